@@ -14,18 +14,23 @@ fn motion_controller_benchmark(c: &mut Criterion) {
         grid: GridConfig {
             cols: 100,
             rows: 100,
+            ..Default::default()
         },
         movement: MovementConfig {
             dash_cells: 10,
             tween_ms: 100,
+            ..Default::default()
         },
         input: InputConfig {
             keyboard_device: None,
             gamepad_device: None,
+            ..Default::default()
         },
         display: DisplayConfig {
             target_monitor: "auto".to_string(),
+            ..Default::default()
         },
+        ..Default::default()
     };
 
     let mut controller = MotionController::new(Arc::new(RwLock::new(config)));
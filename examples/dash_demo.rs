@@ -15,18 +15,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("GridPointer Demo: Dash Movement");
 
     let config = Config {
-        grid: GridConfig { cols: 20, rows: 12 },
+        grid: GridConfig {
+            cols: 20,
+            rows: 12,
+            ..Default::default()
+        },
         movement: MovementConfig {
             dash_cells: 7,
             tween_ms: 300,
+            ..Default::default()
         },
         input: InputConfig {
             keyboard_device: None,
             gamepad_device: None,
+            ..Default::default()
         },
         display: DisplayConfig {
             target_monitor: "auto".to_string(),
+            ..Default::default()
         },
+        ..Default::default()
     };
 
     let mut controller = MotionController::new(Arc::new(RwLock::new(config)));
@@ -35,7 +43,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Starting at center");
 
     // Start at center
-    controller.current_grid_pos = (10, 6);
+    controller.handle_event(MotionEvent::JumpTo { cell: (10, 6) });
     print_position(&controller);
 
     // Perform dash sequence
@@ -56,7 +64,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             if let Some(pos) = controller.update() {
                 print!(
                     "  Position: ({:.3}, {:.3}) Grid: ({}, {})   \r",
-                    pos.0, pos.1, controller.current_grid_pos.0, controller.current_grid_pos.1
+                    pos.0,
+                    pos.1,
+                    controller.grid_position().0,
+                    controller.grid_position().1
                 );
                 std::io::Write::flush(&mut std::io::stdout()).unwrap();
             } else {
@@ -80,6 +91,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 fn print_position(controller: &MotionController) {
     println!(
         "Current grid position: ({}, {})",
-        controller.current_grid_pos.0, controller.current_grid_pos.1
+        controller.grid_position().0,
+        controller.grid_position().1
     );
 }
@@ -15,23 +15,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("GridPointer Demo: Basic Grid Movement");
 
     let config = Config {
-        grid: GridConfig { cols: 10, rows: 6 },
+        grid: GridConfig {
+            cols: 10,
+            rows: 6,
+            ..Default::default()
+        },
         movement: MovementConfig {
             dash_cells: 3,
             tween_ms: 500,
+            ..Default::default()
         },
         input: InputConfig {
             keyboard_device: None,
             gamepad_device: None,
+            ..Default::default()
         },
         display: DisplayConfig {
             target_monitor: "auto".to_string(),
+            ..Default::default()
         },
+        ..Default::default()
     };
 
     let mut controller = MotionController::new(Arc::new(RwLock::new(config)));
 
-    println!("Starting at grid position (0, 0)");
+    println!("Starting at the grid center");
     print_grid_position(&controller);
 
     // Demonstrate basic movements
@@ -74,6 +82,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 fn print_grid_position(controller: &MotionController) {
     println!(
         "Grid position: ({}, {})",
-        controller.current_grid_pos.0, controller.current_grid_pos.1
+        controller.grid_position().0,
+        controller.grid_position().1
     );
 }
@@ -0,0 +1,148 @@
+//! Command-line interface: the daemon and client commands for a running daemon
+
+use crate::config::ConfigOverride;
+use crate::input::Direction;
+use crate::ipc::Request;
+use crate::wl::Button;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "gridpointer",
+    about = "Grid-based cursor control daemon for Wayland",
+    disable_version_flag = true
+)]
+pub struct Cli {
+    /// Print version information
+    #[arg(short = 'V', long)]
+    pub version: bool,
+
+    /// Control socket path [default: $XDG_RUNTIME_DIR/gridpointer.sock]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+
+    /// Runs the daemon in the foreground when omitted
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the daemon
+    Daemon(DaemonArgs),
+    /// Move a number of cells in a direction
+    Move {
+        direction: Direction,
+        #[arg(default_value_t = 1)]
+        cells: u32,
+    },
+    /// Dash in a direction
+    Dash { direction: Direction },
+    /// Move to a cell
+    Jump { col: u32, row: u32 },
+    /// Click a mouse button at the current position
+    Click {
+        #[arg(default_value = "left")]
+        button: Button,
+    },
+    /// Scroll by wheel steps, positive values scroll right and down
+    #[command(allow_negative_numbers = true)]
+    Scroll {
+        #[arg(long, default_value_t = 0)]
+        x: i32,
+        #[arg(long, default_value_t = 0)]
+        y: i32,
+    },
+    /// Show the daemon's grid cell, position, output and mode
+    Status {
+        /// Print the raw JSON response data
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the status, then a JSON line for every state change
+    Subscribe,
+    /// Print the effective configuration, defaults included, as TOML
+    Config {
+        /// Read this file instead of asking the running daemon
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+    },
+    /// Make the daemon re-read its configuration file
+    Reload,
+    /// Switch to a configuration profile, or back to the base configuration without a name
+    Profile { name: Option<String> },
+    /// Turn keyboard and gamepad control on or off
+    Toggle,
+    /// Turn keyboard and gamepad control off
+    Pause,
+    /// Turn keyboard and gamepad control back on
+    Resume,
+    /// Stop the daemon
+    Quit,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct DaemonArgs {
+    /// Configuration file [default: $GRIDPOINTER_CONFIG or $XDG_CONFIG_HOME/gridpointer/config.toml]
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Configuration profile to start with [default: $GRIDPOINTER_PROFILE]
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Override a setting, e.g. `grid.cols=30`; wins over the file and the environment
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    pub overrides: Vec<ConfigOverride>,
+
+    /// Log filter, e.g. `debug` or `gridpointer=trace` [default: $RUST_LOG or info]
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Stay attached to the terminal instead of detaching
+    #[arg(long)]
+    pub foreground: bool,
+
+    /// Ask an already running instance to quit and take over from it
+    #[arg(long)]
+    pub replace: bool,
+}
+
+impl Default for DaemonArgs {
+    /// Running without a subcommand keeps the daemon in the foreground
+    fn default() -> Self {
+        Self {
+            config: None,
+            profile: None,
+            overrides: Vec::new(),
+            log_level: None,
+            foreground: true,
+            replace: false,
+        }
+    }
+}
+
+impl Command {
+    /// Control socket request for a client command, `None` for `daemon`
+    pub fn request(&self) -> Option<Request> {
+        let request = match *self {
+            Command::Profile { ref name } => Request::Profile { name: name.clone() },
+            Command::Daemon(_) => return None,
+            Command::Move { direction, cells } => Request::Move { direction, cells },
+            Command::Dash { direction } => Request::Dash { direction },
+            Command::Jump { col, row } => Request::Jump { col, row },
+            Command::Click { button } => Request::Click { button },
+            Command::Scroll { x, y } => Request::Scroll { x, y },
+            Command::Status { .. } => Request::Status,
+            Command::Subscribe => Request::Subscribe,
+            Command::Config { .. } => Request::Config,
+            Command::Reload => Request::Reload,
+            Command::Toggle => Request::Toggle,
+            Command::Pause => Request::Pause,
+            Command::Resume => Request::Resume,
+            Command::Quit => Request::Quit,
+        };
+        Some(request)
+    }
+}
@@ -1,79 +1,886 @@
 //! Configuration management with hot-reload support
 
 use crate::error::{GridPointerError, Result};
+use crate::focus::FocusRule;
+use crate::layers::Layers;
+use crate::marks::Mark;
+use crate::migrate::{self, CONFIG_VERSION};
 use anyhow::Context;
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{RwLock, broadcast};
-use tracing::{info, warn};
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
 
 /// Main configuration structure
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Config {
+    /// Layout version the file was written for, see [`crate::migrate`]
+    pub version: u32,
     pub grid: GridConfig,
     pub movement: MovementConfig,
     pub input: InputConfig,
     pub display: DisplayConfig,
+    /// Predefined marks, keyed by a single character
+    pub marks: BTreeMap<String, Mark>,
+    /// Profiles to switch to while matching windows have focus, first match wins
+    pub rules: Vec<FocusRule>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Layout of the grid over its target area
+///
+/// By default `cols` by `rows` positions are spread evenly from edge to edge.
+/// `col_bounds` and `row_bounds` place the cell boundaries explicitly instead,
+/// and `anchor` picks whether the cursor goes to the boundaries or to the
+/// middle of the cells between them. Use [`GridConfig::size`] for the number
+/// of positions rather than `cols` and `rows`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
 pub struct GridConfig {
     pub cols: u32,
     pub rows: u32,
+    /// Column boundaries as increasing fractions of the width, replacing `cols` when set
+    pub col_bounds: Vec<f64>,
+    /// Row boundaries as increasing fractions of the height, replacing `rows` when set
+    pub row_bounds: Vec<f64>,
+    /// Which point of a cell the cursor is placed on
+    pub anchor: CellAnchor,
+    /// Space left free along each edge, as a fraction of the area's height or width
+    pub margin_top: f64,
+    pub margin_right: f64,
+    pub margin_bottom: f64,
+    pub margin_left: f64,
+    /// What happens when a move runs off the left or right edge
+    pub edge_x: EdgeMode,
+    /// What happens when a move runs off the top or bottom edge
+    pub edge_y: EdgeMode,
+    /// Whether a clamped move that would overshoot the edge stops there or is refused
+    pub overshoot: Overshoot,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Where in a cell the cursor is placed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CellAnchor {
+    /// On the cell boundaries, so the outermost positions are on the edges
+    #[default]
+    Corner,
+    /// In the middle of the cells between the boundaries
+    Center,
+}
+
+/// Edge behavior for one grid axis
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeMode {
+    /// Stay on the edge cell
+    #[default]
+    Clamp,
+    /// Continue from the opposite edge
+    Wrap,
+    /// Move onto the neighbouring output, if there is one
+    NextOutput,
+}
+
+/// Overshoot behavior for moves that would leave the grid
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Overshoot {
+    /// Stop exactly at the edge
+    #[default]
+    Stop,
+    /// Don't move at all
+    Refuse,
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self {
+            cols: 20,
+            rows: 12,
+            col_bounds: Vec::new(),
+            row_bounds: Vec::new(),
+            anchor: CellAnchor::Corner,
+            margin_top: 0.0,
+            margin_right: 0.0,
+            margin_bottom: 0.0,
+            margin_left: 0.0,
+            edge_x: EdgeMode::Clamp,
+            edge_y: EdgeMode::Clamp,
+            overshoot: Overshoot::Stop,
+        }
+    }
+}
+
+impl GridConfig {
+    /// Number of cursor positions across and down
+    pub fn size(&self) -> (u32, u32) {
+        (
+            positions(&self.col_bounds, self.cols, self.anchor),
+            positions(&self.row_bounds, self.rows, self.anchor),
+        )
+    }
+}
+
+/// Positions along one axis: one per boundary, or one per cell between them
+fn positions(bounds: &[f64], uniform: u32, anchor: CellAnchor) -> u32 {
+    if bounds.is_empty() {
+        return uniform;
+    }
+    let count = bounds.len() as u32;
+    match anchor {
+        CellAnchor::Corner => count,
+        CellAnchor::Center => count.saturating_sub(1),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
 pub struct MovementConfig {
     pub dash_cells: u32,
     pub tween_ms: u64,
+    /// How the tween duration is derived from the distance travelled
+    pub duration_mode: DurationMode,
+    /// Milliseconds per cell traversed (`per_cell` mode)
+    pub ms_per_cell: u64,
+    /// Cursor speed in pixels per second (`speed` mode)
+    pub speed_px_per_sec: f64,
+    /// Lower bound for distance-proportional durations
+    pub min_tween_ms: u64,
+    /// Upper bound for distance-proportional durations
+    pub max_tween_ms: u64,
+    /// Number of positions kept for back/forward navigation
+    pub history_size: usize,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Tween duration modes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DurationMode {
+    /// Every move takes exactly `tween_ms`
+    #[default]
+    Fixed,
+    /// `ms_per_cell` for each cell traversed, clamped to `min_tween_ms..=max_tween_ms`
+    PerCell,
+    /// Constant `speed_px_per_sec`, clamped to `min_tween_ms..=max_tween_ms`
+    Speed,
+}
+
+impl Default for MovementConfig {
+    fn default() -> Self {
+        Self {
+            dash_cells: 5,
+            tween_ms: 150,
+            duration_mode: DurationMode::Fixed,
+            ms_per_cell: 30,
+            speed_px_per_sec: 4000.0,
+            min_tween_ms: 60,
+            max_tween_ms: 400,
+            history_size: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
 pub struct InputConfig {
     pub keyboard_device: Option<String>,
     pub gamepad_device: Option<String>,
+    /// How long an incomplete key command (count, operator, mark) waits for the next key
+    pub pending_timeout_ms: u64,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            keyboard_device: None,
+            gamepad_device: None,
+            pending_timeout_ms: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
 pub struct DisplayConfig {
     pub target_monitor: String,
+    /// Cursor updates per second while moving; follows the output's refresh rate if unset
+    pub update_rate_hz: Option<u32>,
+    /// What the grid is spread over
+    pub grid_target: GridTarget,
+}
+
+/// Area the grid covers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GridTarget {
+    /// The whole active output
+    #[default]
+    Output,
+    /// The focused window, following focus; the whole output if its geometry is unknown
+    ///
+    /// Only Hyprland reports the focused window. It has no resize event, so a
+    /// resized window's bounds are picked up at the next move.
+    Window,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            target_monitor: "auto".to_string(),
+            update_rate_hz: None,
+            grid_target: GridTarget::Output,
+        }
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            grid: GridConfig { cols: 20, rows: 12 },
-            movement: MovementConfig {
-                dash_cells: 5,
-                tween_ms: 150,
-            },
-            input: InputConfig {
-                keyboard_device: None,
-                gamepad_device: None,
-            },
-            display: DisplayConfig {
-                target_monitor: "auto".to_string(),
-            },
+            version: CONFIG_VERSION,
+            grid: GridConfig::default(),
+            movement: MovementConfig::default(),
+            input: InputConfig::default(),
+            display: DisplayConfig::default(),
+            marks: BTreeMap::new(),
+            rules: Vec::new(),
         }
     }
 }
 
+impl Config {
+    /// Parse and validate a TOML configuration
+    ///
+    /// Validation problems are all reported together, located in `content`.
+    #[allow(dead_code)] // The daemon loads files through `Layers`
+    pub fn from_toml(content: &str) -> Result<Self> {
+        Self::from_toml_with(content, &[])
+    }
+
+    /// Parse a TOML configuration with `overrides` applied on top, then validate it
+    ///
+    /// Problems with an overridden value name the override instead of a location.
+    /// Included files are looked up relative to the working directory; use
+    /// [`Layers`] to load a file and what it includes.
+    #[allow(dead_code)]
+    pub fn from_toml_with(content: &str, overrides: &[ConfigOverride]) -> Result<Self> {
+        Layers::load(Path::new(""), content)?.build(None, overrides)
+    }
+
+    /// Whether `key`, e.g. `grid.cols`, names a single setting
+    pub fn is_setting(key: &str) -> bool {
+        let Ok(defaults) = serde_json::to_value(Config::default()) else {
+            return false;
+        };
+        let mut known = &defaults;
+        for part in key.split('.') {
+            match known.get(part) {
+                Some(next) => known = next,
+                // Tables without fixed keys, like `marks`, take anything
+                None => return known.as_object().is_some_and(|table| table.is_empty()),
+            }
+        }
+        known.as_object().is_none_or(|table| table.is_empty())
+    }
+
+    /// Keys in `content` that don't match any setting and are ignored
+    pub fn unknown_keys(content: &str) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        let (Ok(document), Ok(settings)) = (
+            toml_edit::ImDocument::parse(content),
+            serde_json::to_value(Config::default()),
+        ) else {
+            return issues;
+        };
+
+        // `include` and profiles are resolved before settings are read, see [`Layers`]
+        let mut known = settings.clone();
+        if let Some(known) = known.as_object_mut() {
+            known.insert("include".to_string(), serde_json::Value::Array(Vec::new()));
+            known.insert(
+                "profile".to_string(),
+                serde_json::Value::Object(Default::default()),
+            );
+        }
+        collect_unknown_keys(document.as_item(), &known, "", &mut issues);
+        if let Some(profiles) = document
+            .get("profile")
+            .and_then(|item| item.as_table_like())
+        {
+            for (name, profile) in profiles.iter() {
+                let prefix = format!("profile.{}.", name);
+                collect_unknown_keys(profile, &settings, &prefix, &mut issues);
+            }
+        }
+        for issue in &mut issues {
+            issue.locate(content, document.as_item());
+        }
+        issues
+    }
+
+    /// Check ranges and constraints between fields, returning every problem found
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        let mut check = |ok: bool, key: &str, message: String| {
+            if !ok {
+                issues.push(ConfigIssue::new(key, message));
+            }
+        };
+
+        check(
+            self.version <= CONFIG_VERSION,
+            "version",
+            format!("this build reads up to version {}", CONFIG_VERSION),
+        );
+
+        // Bounds replace cols and rows on their axis, and are checked below
+        let grid = &self.grid;
+        check(
+            grid.cols >= 2 || !grid.col_bounds.is_empty(),
+            "grid.cols",
+            format!("must be at least 2, got {}", grid.cols),
+        );
+        check(
+            grid.rows >= 2 || !grid.row_bounds.is_empty(),
+            "grid.rows",
+            format!("must be at least 2, got {}", grid.rows),
+        );
+        let (cols, rows) = grid.size();
+        for (key, bounds, count) in [
+            ("grid.col_bounds", &grid.col_bounds, cols),
+            ("grid.row_bounds", &grid.row_bounds, rows),
+        ] {
+            if bounds.is_empty() {
+                continue;
+            }
+            check(
+                count >= 2,
+                key,
+                format!(
+                    "gives {} positions with this anchor, at least 2 are needed",
+                    count
+                ),
+            );
+            for (i, &bound) in bounds.iter().enumerate() {
+                let previous = i.checked_sub(1).map(|i| bounds[i]);
+                check(
+                    (0.0..=1.0).contains(&bound),
+                    &format!("{}.{}", key, i),
+                    format!("must be between 0 and 1, got {}", bound),
+                );
+                check(
+                    previous.is_none_or(|previous| bound > previous),
+                    &format!("{}.{}", key, i),
+                    "must be greater than the boundary before it".to_string(),
+                );
+            }
+        }
+        for (key, margin) in [
+            ("grid.margin_top", grid.margin_top),
+            ("grid.margin_right", grid.margin_right),
+            ("grid.margin_bottom", grid.margin_bottom),
+            ("grid.margin_left", grid.margin_left),
+        ] {
+            check(
+                (0.0..1.0).contains(&margin),
+                key,
+                format!("must be at least 0 and below 1, got {}", margin),
+            );
+        }
+        check(
+            grid.margin_left + grid.margin_right < 1.0,
+            "grid.margin_right",
+            "leaves no width for the grid together with grid.margin_left".to_string(),
+        );
+        check(
+            grid.margin_top + grid.margin_bottom < 1.0,
+            "grid.margin_bottom",
+            "leaves no height for the grid together with grid.margin_top".to_string(),
+        );
+
+        let movement = &self.movement;
+        check(
+            movement.dash_cells >= 1,
+            "movement.dash_cells",
+            "must be at least 1".to_string(),
+        );
+        check(
+            movement.duration_mode != DurationMode::PerCell || movement.ms_per_cell > 0,
+            "movement.ms_per_cell",
+            "must be greater than 0 when duration_mode is \"per_cell\"".to_string(),
+        );
+        check(
+            movement.duration_mode != DurationMode::Speed
+                || (movement.speed_px_per_sec.is_finite() && movement.speed_px_per_sec > 0.0),
+            "movement.speed_px_per_sec",
+            format!(
+                "must be a positive number when duration_mode is \"speed\", got {}",
+                movement.speed_px_per_sec
+            ),
+        );
+        check(
+            movement.min_tween_ms <= movement.max_tween_ms,
+            "movement.min_tween_ms",
+            format!(
+                "must not exceed movement.max_tween_ms ({} > {})",
+                movement.min_tween_ms, movement.max_tween_ms
+            ),
+        );
+        check(
+            movement.history_size >= 1,
+            "movement.history_size",
+            "must be at least 1".to_string(),
+        );
+
+        check(
+            self.input.pending_timeout_ms > 0,
+            "input.pending_timeout_ms",
+            "must be greater than 0".to_string(),
+        );
+
+        check(
+            !self.display.target_monitor.is_empty(),
+            "display.target_monitor",
+            "must be an output name or \"auto\"".to_string(),
+        );
+        if let Some(hz) = self.display.update_rate_hz {
+            check(
+                (1..=1000).contains(&hz),
+                "display.update_rate_hz",
+                format!("must be between 1 and 1000, got {}", hz),
+            );
+        }
+
+        for (key, mark) in &self.marks {
+            let path = format!("marks.{}", key);
+            check(
+                key.chars().count() == 1,
+                &path,
+                "mark names must be a single character".to_string(),
+            );
+            check(
+                mark.col < cols,
+                &format!("{}.col", path),
+                format!("{} is outside the grid's {} columns", mark.col, cols),
+            );
+            check(
+                mark.row < rows,
+                &format!("{}.row", path),
+                format!("{} is outside the grid's {} rows", mark.row, rows),
+            );
+        }
+
+        for (i, rule) in self.rules.iter().enumerate() {
+            check(
+                rule.app_id.is_some() || rule.title.is_some(),
+                &format!("rules.{}", i),
+                "needs an app_id or a title to match".to_string(),
+            );
+        }
+
+        issues
+    }
+}
+
+/// A group of settings that differs after a reload
+///
+/// Subsystems read the new values from the shared config; this only says
+/// what to re-apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigChange {
+    Grid,
+    Movement,
+    KeyboardDevice,
+    GamepadDevice,
+    PendingTimeout,
+    TargetMonitor,
+    UpdateRate,
+    GridTarget,
+    Marks,
+    Rules,
+}
+
+impl ConfigChange {
+    /// Whether `changes` need the cursor re-placed even if the grid's region stays put
+    ///
+    /// A new grid changes the cell positions, and a new target monitor moves
+    /// the cursor to another output.
+    pub fn repositions(changes: &[ConfigChange]) -> bool {
+        changes
+            .iter()
+            .any(|change| matches!(change, ConfigChange::Grid | ConfigChange::TargetMonitor))
+    }
+}
+
+impl Config {
+    /// What changed going from `self` to `new`
+    pub fn diff(&self, new: &Config) -> Vec<ConfigChange> {
+        [
+            (self.grid != new.grid, ConfigChange::Grid),
+            (self.movement != new.movement, ConfigChange::Movement),
+            (
+                self.input.keyboard_device != new.input.keyboard_device,
+                ConfigChange::KeyboardDevice,
+            ),
+            (
+                self.input.gamepad_device != new.input.gamepad_device,
+                ConfigChange::GamepadDevice,
+            ),
+            (
+                self.input.pending_timeout_ms != new.input.pending_timeout_ms,
+                ConfigChange::PendingTimeout,
+            ),
+            (
+                self.display.target_monitor != new.display.target_monitor,
+                ConfigChange::TargetMonitor,
+            ),
+            (
+                self.display.update_rate_hz != new.display.update_rate_hz,
+                ConfigChange::UpdateRate,
+            ),
+            (
+                self.display.grid_target != new.display.grid_target,
+                ConfigChange::GridTarget,
+            ),
+            (self.marks != new.marks, ConfigChange::Marks),
+            (self.rules != new.rules, ConfigChange::Rules),
+        ]
+        .into_iter()
+        .filter_map(|(changed, change)| changed.then_some(change))
+        .collect()
+    }
+}
+
+/// A problem with one configuration value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// Dotted key path, e.g. `grid.cols`
+    pub key: String,
+    pub message: String,
+    /// One-based line and column of the value, or of its closest enclosing table
+    pub location: Option<(usize, usize)>,
+}
+
+impl ConfigIssue {
+    pub(crate) fn new(key: &str, message: String) -> Self {
+        Self {
+            key: key.to_string(),
+            message,
+            location: None,
+        }
+    }
+
+    /// Find the value in the parsed document, falling back to the nearest parent
+    /// for keys that were left out
+    pub(crate) fn locate(&mut self, content: &str, root: &toml_edit::Item) {
+        let mut item = root;
+        let mut span = None;
+        for part in self.key.split('.') {
+            let child = match part.parse::<usize>() {
+                Ok(index) => item.get(index),
+                Err(_) => item.get(part),
+            };
+            let Some(child) = child else {
+                break;
+            };
+            item = child;
+            span = item.span().or(span);
+        }
+        self.location = span.map(|span| line_column(content, span.start));
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((line, column)) = self.location {
+            write!(f, "line {}, column {}: ", line, column)?;
+        }
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+/// Add an issue for each key of `item` missing from the serialized defaults in `known`
+fn collect_unknown_keys(
+    item: &toml_edit::Item,
+    known: &serde_json::Value,
+    prefix: &str,
+    issues: &mut Vec<ConfigIssue>,
+) {
+    let (Some(table), Some(known)) = (item.as_table_like(), known.as_object()) else {
+        return;
+    };
+    // Maps such as [marks] are empty by default and take any key
+    if known.is_empty() {
+        return;
+    }
+
+    for (key, child) in table.iter() {
+        let path = format!("{}{}", prefix, key);
+        match known.get(key) {
+            Some(known) => collect_unknown_keys(child, known, &format!("{}.", path), issues),
+            None => issues.push(ConfigIssue::new(&path, "unknown key, ignored".to_string())),
+        }
+    }
+}
+
+/// One-based line and column of a byte offset
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
+}
+
+/// A setting given outside the config file, on top of it
+///
+/// Parsed from `key=value` as given to `--set`. Values are TOML, except that
+/// anything that isn't valid TOML is taken as a string, so `DP-1` needs no
+/// quotes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigOverride {
+    /// Dotted key, e.g. `grid.cols`
+    pub key: String,
+    pub value: toml::Value,
+    /// `--set` or the environment variable it came from
+    pub source: String,
+}
+
+impl ConfigOverride {
+    /// Overrides from `GRIDPOINTER_<SECTION>_<KEY>` environment variables
+    pub fn from_env() -> Vec<Self> {
+        Self::from_vars(std::env::vars())
+    }
+
+    /// Overrides from `GRIDPOINTER_<SECTION>_<KEY>` variables among `vars`
+    ///
+    /// Variables that don't name a setting are ignored with a warning.
+    pub fn from_vars(vars: impl IntoIterator<Item = (String, String)>) -> Vec<Self> {
+        let mut overrides: Vec<Self> = vars
+            .into_iter()
+            .filter(|(name, _)| name != CONFIG_PATH_VAR)
+            .filter_map(|(name, value)| {
+                let (section, key) = name.strip_prefix("GRIDPOINTER_")?.split_once('_')?;
+                let key = format!("{}.{}", section, key).to_lowercase();
+                if !Config::is_setting(&key) {
+                    warn!("Ignoring {}: there is no setting {}", name, key);
+                    return None;
+                }
+                Some(Self {
+                    key,
+                    value: parse_value(&value),
+                    source: name,
+                })
+            })
+            .collect();
+        // Environment order is arbitrary
+        overrides.sort_by(|a, b| a.source.cmp(&b.source));
+        overrides
+    }
+
+    /// Set the value in a parsed config file, creating tables as needed
+    pub(crate) fn apply(&self, table: &mut toml::Table) {
+        let mut parts: Vec<&str> = self.key.split('.').collect();
+        let Some(last) = parts.pop() else {
+            return;
+        };
+
+        let mut table = table;
+        for part in parts {
+            let entry = table
+                .entry(part)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if !entry.is_table() {
+                *entry = toml::Value::Table(toml::Table::new());
+            }
+            let toml::Value::Table(next) = entry else {
+                unreachable!()
+            };
+            table = next;
+        }
+        table.insert(last.to_string(), self.value.clone());
+    }
+
+    /// Whether this override set the value `key` refers to
+    pub(crate) fn covers(&self, key: &str) -> bool {
+        key == self.key
+            || key
+                .strip_prefix(self.key.as_str())
+                .is_some_and(|rest| rest.starts_with('.'))
+    }
+}
+
+impl std::str::FromStr for ConfigOverride {
+    type Err = GridPointerError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| GridPointerError::Config(format!("expected KEY=VALUE, got {}", s)))?;
+        let key = key.trim();
+        if !Config::is_setting(key) {
+            return Err(GridPointerError::Config(format!(
+                "there is no setting {}",
+                key
+            )));
+        }
+
+        Ok(Self {
+            key: key.to_string(),
+            value: parse_value(value.trim()),
+            source: "--set".to_string(),
+        })
+    }
+}
+
+/// A TOML value, or a string if `raw` isn't one
+fn parse_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+/// Environment variable naming the config file
+pub const CONFIG_PATH_VAR: &str = "GRIDPOINTER_CONFIG";
+
+/// Quiet period after the last file event before reloading
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// The config files as named (made absolute) and as resolved through
+/// symlinks, and the directories being watched for them
+#[derive(Default)]
+struct WatchedFiles {
+    files: Vec<PathBuf>,
+    dirs: Vec<PathBuf>,
+}
+
+impl WatchedFiles {
+    /// Re-resolve `paths` and move the watches to match
+    fn update(&mut self, watcher: &mut impl Watcher, paths: &[PathBuf]) -> notify::Result<()> {
+        let mut files = Vec::new();
+        for path in paths {
+            // Events carry absolute paths, and a bare file name has no directory to watch
+            let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+            files.push(path.clone());
+            if let Ok(target) = std::fs::canonicalize(&path) {
+                if target != path {
+                    files.push(target);
+                }
+            }
+        }
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for dir in files.iter().filter_map(|file| file.parent()) {
+            if !dirs.iter().any(|known| known == dir) {
+                dirs.push(dir.to_path_buf());
+            }
+        }
+
+        for dir in self.dirs.iter().filter(|dir| !dirs.contains(dir)) {
+            let _ = watcher.unwatch(dir);
+        }
+        self.dirs.retain(|dir| dirs.contains(dir));
+        self.files = files;
+
+        // Directories that failed are tried again on the next update
+        let mut result = Ok(());
+        for dir in dirs {
+            if self.dirs.contains(&dir) {
+                continue;
+            }
+            match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    debug!("Watching {}", dir.display());
+                    self.dirs.push(dir);
+                }
+                Err(e) => result = Err(e),
+            }
+        }
+        result
+    }
+
+    fn matches(&self, event: &Event) -> bool {
+        event.paths.iter().any(|path| self.files.contains(path))
+    }
+}
+
+/// Sleep until `deadline`; never completes without one
+async fn settled(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Which `[profile.<name>]` applies
+#[derive(Debug, Clone, Default)]
+struct Selection {
+    /// Chosen at startup or with [`ConfigManager::set_profile`]
+    chosen: Option<String>,
+    /// Picked by a rule for the focused window, wins over `chosen`
+    window: Option<String>,
+}
+
+impl Selection {
+    fn active(&self) -> Option<&str> {
+        self.window.as_deref().or(self.chosen.as_deref())
+    }
+}
+
 /// Configuration manager with hot-reload support
 pub struct ConfigManager {
     config_path: PathBuf,
+    /// Applied on top of the file at every load
+    overrides: Vec<ConfigOverride>,
+    profile: RwLock<Selection>,
+    /// The config file and everything it includes, as of the last load
+    layers: RwLock<Layers>,
     config: Arc<RwLock<Config>>,
+    reloaded: broadcast::Sender<Vec<ConfigChange>>,
 }
 
 impl ConfigManager {
-    pub async fn new() -> anyhow::Result<Self> {
-        let config_path = Self::get_config_path()?;
-        let config = Self::load_or_create_config(&config_path).await?;
+    /// Load the configuration from `config_path` without overrides
+    #[allow(dead_code)] // The daemon always goes through `open`
+    pub async fn with_path(config_path: PathBuf) -> anyhow::Result<Self> {
+        Self::open(config_path, None, Vec::new()).await
+    }
+
+    /// Load the configuration from `config_path` with `profile` and `overrides` on top
+    ///
+    /// Later overrides win over earlier ones, and all of them over the file and profile.
+    pub async fn open(
+        config_path: PathBuf,
+        profile: Option<String>,
+        overrides: Vec<ConfigOverride>,
+    ) -> anyhow::Result<Self> {
+        for setting in &overrides {
+            info!(
+                "{} = {} (from {})",
+                setting.key, setting.value, setting.source
+            );
+        }
+        let (config, layers) =
+            Self::load_or_create_config(&config_path, profile.as_deref(), &overrides).await?;
+        if let Some(profile) = &profile {
+            info!("Using profile {}", profile);
+        }
 
         Ok(Self {
             config_path,
+            overrides,
+            profile: RwLock::new(Selection {
+                chosen: profile,
+                window: None,
+            }),
+            layers: RwLock::new(layers),
             config: Arc::new(RwLock::new(config)),
+            reloaded: broadcast::channel(16).0,
         })
     }
 
@@ -81,17 +888,25 @@ impl ConfigManager {
         self.config.clone()
     }
 
+    /// Receive what changed after each successful reload
+    pub fn subscribe_reloads(&self) -> broadcast::Receiver<Vec<ConfigChange>> {
+        self.reloaded.subscribe()
+    }
+
     /// Watch for configuration file changes and reload automatically
+    ///
+    /// The directories holding the file and, for a symlink, its target are
+    /// watched rather than the file itself. That way editors that save by
+    /// replacing the file and links that get re-pointed are both seen. A burst
+    /// of events from one save becomes a single reload.
     pub async fn watch_config(&self, mut shutdown: broadcast::Receiver<()>) -> anyhow::Result<()> {
         let (tx, mut rx) = tokio::sync::mpsc::channel(100);
-        let config_path = self.config_path.clone();
-        let config = self.config.clone();
 
         // Setup file watcher
         let mut watcher: RecommendedWatcher = notify::Watcher::new(
             move |result: notify::Result<Event>| {
                 if let Ok(event) = result {
-                    if matches!(event.kind, EventKind::Modify(_)) {
+                    if !matches!(event.kind, EventKind::Access(_)) {
                         let _ = tx.blocking_send(event);
                     }
                 }
@@ -99,21 +914,28 @@ impl ConfigManager {
             notify::Config::default(),
         )?;
 
-        if let Some(parent) = config_path.parent() {
-            watcher.watch(parent, RecursiveMode::NonRecursive)?;
-        }
+        let mut watched = WatchedFiles::default();
+        watched.update(&mut watcher, &self.source_paths().await)?;
 
+        let mut deadline = None;
         loop {
             tokio::select! {
-                Some(_event) = rx.recv() => {
-                    match Self::load_config(&config_path).await {
-                        Ok(new_config) => {
-                            *config.write().await = new_config;
-                            info!("Configuration reloaded");
-                        }
-                        Err(e) => {
-                            warn!("Failed to reload config: {}", e);
-                        }
+                Some(event) = rx.recv() => {
+                    if watched.matches(&event) {
+                        debug!("Config file event: {:?}", event.kind);
+                        deadline = Some(Instant::now() + RELOAD_DEBOUNCE);
+                    }
+                }
+                _ = settled(deadline) => {
+                    deadline = None;
+                    if !self.config_path.exists() {
+                        debug!("{} was removed, waiting for it to come back", self.config_path.display());
+                    } else if let Err(e) = self.reload().await {
+                        warn!("Keeping the previous configuration: {:#}", e);
+                    }
+                    // Links may point somewhere else now, and includes may have changed
+                    if let Err(e) = watched.update(&mut watcher, &self.source_paths().await) {
+                        warn!("Failed to watch the config files: {}", e);
                     }
                 }
                 _ = shutdown.recv() => {
@@ -125,29 +947,190 @@ impl ConfigManager {
         Ok(())
     }
 
-    fn get_config_path() -> anyhow::Result<PathBuf> {
+    /// Profile in use, `None` for the base configuration
+    ///
+    /// A profile picked for the focused window wins over the chosen one.
+    pub async fn profile(&self) -> Option<String> {
+        self.profile.read().await.active().map(str::to_string)
+    }
+
+    /// Switch to another profile, or back to the base configuration with `None`
+    ///
+    /// The files are re-read as for [`reload`](Self::reload). If the profile
+    /// doesn't exist or doesn't load, the current one stays selected.
+    pub async fn set_profile(&self, profile: Option<String>) -> anyhow::Result<Vec<ConfigChange>> {
+        let mut selection = self.profile.write().await;
+        let next = Selection {
+            chosen: profile,
+            window: selection.window.clone(),
+        };
+        let changes = self.apply_reload(next.active()).await?;
+        match &next.chosen {
+            Some(profile) => info!("Switched to profile {}", profile),
+            None => info!("Switched to the base configuration"),
+        }
+        *selection = next;
+        Ok(changes)
+    }
+
+    /// Use `profile` on top of the chosen one while the focused window calls for it
+    ///
+    /// Applied like a reload, from the files as last read. Nothing is sent
+    /// when the profile in use stays the same.
+    pub async fn set_window_profile(
+        &self,
+        profile: Option<String>,
+    ) -> anyhow::Result<Vec<ConfigChange>> {
+        let mut selection = self.profile.write().await;
+        let next = Selection {
+            chosen: selection.chosen.clone(),
+            window: profile,
+        };
+        if next.active() == selection.active() {
+            *selection = next;
+            return Ok(Vec::new());
+        }
+
+        let config = self
+            .layers
+            .read()
+            .await
+            .build(next.active(), &self.overrides)?;
+        match next.active() {
+            Some(profile) => info!("Switched to profile {} for the focused window", profile),
+            None => info!("Switched to the base configuration for the focused window"),
+        }
+        *selection = next;
+        Ok(self.replace(config).await)
+    }
+
+    /// Re-read the configuration file, keeping the current config if it fails to load
+    ///
+    /// Returns what changed, which is also sent to reload subscribers.
+    pub async fn reload(&self) -> anyhow::Result<Vec<ConfigChange>> {
+        let selection = self.profile.read().await;
+        let changes = self.apply_reload(selection.active()).await?;
+        info!("Configuration reloaded");
+        Ok(changes)
+    }
+
+    async fn apply_reload(&self, profile: Option<&str>) -> anyhow::Result<Vec<ConfigChange>> {
+        let (new_config, layers) =
+            Self::load_config(&self.config_path, profile, &self.overrides).await?;
+        *self.layers.write().await = layers;
+        Ok(self.replace(new_config).await)
+    }
+
+    /// Swap in `new_config` and tell subscribers what changed
+    async fn replace(&self, new_config: Config) -> Vec<ConfigChange> {
+        let changes = {
+            let mut config = self.config.write().await;
+            let changes = config.diff(&new_config);
+            *config = new_config;
+            changes
+        };
+
+        debug!("Changed: {:?}", changes);
+        let _ = self.reloaded.send(changes.clone());
+        changes
+    }
+
+    /// The config file and everything it includes, as of the last load
+    async fn source_paths(&self) -> Vec<PathBuf> {
+        self.layers
+            .read()
+            .await
+            .sources()
+            .iter()
+            .map(|source| source.path.clone())
+            .collect()
+    }
+
+    /// Config file to use: `explicit` (from `--config`), else `$GRIDPOINTER_CONFIG`,
+    /// else `$XDG_CONFIG_HOME/gridpointer/config.toml`
+    pub fn resolve_path(explicit: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+        if let Some(path) = explicit {
+            return Ok(path);
+        }
+        if let Some(path) = std::env::var_os(CONFIG_PATH_VAR).filter(|path| !path.is_empty()) {
+            return Ok(PathBuf::from(path));
+        }
+        Self::default_path()
+    }
+
+    /// `$XDG_CONFIG_HOME/gridpointer/config.toml`, created with defaults if missing
+    pub fn default_path() -> anyhow::Result<PathBuf> {
         let mut path = dirs::config_dir().context("Could not determine config directory")?;
         path.push("gridpointer");
-        std::fs::create_dir_all(&path)?;
         path.push("config.toml");
         Ok(path)
     }
 
-    async fn load_or_create_config(path: &PathBuf) -> anyhow::Result<Config> {
-        if path.exists() {
-            Self::load_config(path).await
-        } else {
-            let config = Config::default();
-            Self::save_config(path, &config).await?;
+    async fn load_or_create_config(
+        path: &PathBuf,
+        profile: Option<&str>,
+        overrides: &[ConfigOverride],
+    ) -> anyhow::Result<(Config, Layers)> {
+        if !path.exists() {
+            // A path given explicitly that doesn't exist is most likely a typo
+            if Self::default_path().ok().as_ref() != Some(path) {
+                anyhow::bail!("Config file {} does not exist", path.display());
+            }
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            Self::save_config(path, &Config::default()).await?;
             info!("Created default configuration at {}", path.display());
-            Ok(config)
         }
+        Self::load_config(path, profile, overrides).await
     }
 
-    async fn load_config(path: &PathBuf) -> anyhow::Result<Config> {
+    /// Load the file at `path` and what it includes
+    async fn load_config(
+        path: &PathBuf,
+        profile: Option<&str>,
+        overrides: &[ConfigOverride],
+    ) -> anyhow::Result<(Config, Layers)> {
         let content = tokio::fs::read_to_string(path).await?;
-        let config: Config = toml::from_str(&content)?;
-        Ok(config)
+        let upgrade = migrate::upgrade(&content)
+            .with_context(|| format!("Failed to upgrade {}", path.display()))?;
+        let content = upgrade
+            .as_ref()
+            .map_or(content.as_str(), |upgrade| &upgrade.content);
+
+        let layers = Layers::load(path, content)
+            .and_then(|layers| Ok((layers.build(profile, overrides)?, layers)));
+        let (config, layers) =
+            layers.with_context(|| format!("Failed to load {}", path.display()))?;
+        for (file, issue) in layers.unknown_keys() {
+            warn!("{}: {}", file.display(), issue);
+        }
+        // Catch mistakes in other profiles before switching to them
+        for name in layers.profiles().filter(|name| Some(*name) != profile) {
+            if let Err(e) = layers.build(Some(name), overrides) {
+                warn!("Profile {} would not load: {}", name, e);
+            }
+        }
+
+        // Only rewrite the file once the upgraded config is known to load
+        if let Some(upgrade) = &upgrade {
+            let mut backup = path.clone().into_os_string();
+            backup.push(format!(".v{}.bak", upgrade.from));
+            tokio::fs::copy(path, &backup).await?;
+            tokio::fs::write(path, &upgrade.content).await?;
+
+            info!(
+                "Upgraded {} from version {} to {}, original saved as {}",
+                path.display(),
+                upgrade.from,
+                CONFIG_VERSION,
+                PathBuf::from(backup).display()
+            );
+            for change in &upgrade.changes {
+                info!("  {}", change);
+            }
+        }
+        Ok((config, layers))
     }
 
     async fn save_config(path: &PathBuf, config: &Config) -> anyhow::Result<()> {
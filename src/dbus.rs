@@ -0,0 +1,252 @@
+//! Session bus interface mirroring the control socket
+//!
+//! Exports `org.gridpointer.Daemon` at `/org/gridpointer/Daemon`. Method calls
+//! go through the same request channel as the control socket, and properties
+//! follow the daemon's state-change events.
+
+use crate::input::Direction;
+use crate::ipc::{self, Command, Event, Request, Response};
+use crate::wl::Button;
+use clap::ValueEnum;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+use zbus::object_server::{InterfaceRef, SignalEmitter};
+use zbus::{Connection, fdo, interface};
+
+/// Well-known bus name claimed by the daemon
+pub const BUS_NAME: &str = "org.gridpointer.Daemon";
+
+/// Path of the exported object
+pub const OBJECT_PATH: &str = "/org/gridpointer/Daemon";
+
+/// The exported D-Bus object, caching state for its properties
+pub struct DaemonInterface {
+    tx: mpsc::UnboundedSender<Command>,
+    cell: (u32, u32),
+    output: String,
+    active: bool,
+    profile: String,
+}
+
+impl DaemonInterface {
+    fn new(tx: mpsc::UnboundedSender<Command>, status: &Response) -> Self {
+        let data = status.data.clone().unwrap_or_default();
+        let cell = |axis: &str| data["cell"][axis].as_u64().unwrap_or_default() as u32;
+
+        Self {
+            tx,
+            cell: (cell("col"), cell("row")),
+            output: data["output"].as_str().unwrap_or_default().to_string(),
+            active: data["active"].as_bool().unwrap_or(true),
+            profile: data["profile"].as_str().unwrap_or_default().to_string(),
+        }
+    }
+
+    async fn request(&self, request: Request) -> fdo::Result<Response> {
+        let response = ipc::dispatch(&self.tx, request).await;
+        if response.ok {
+            Ok(response)
+        } else {
+            Err(fdo::Error::Failed(response.error.unwrap_or_default()))
+        }
+    }
+}
+
+fn parse_direction(direction: &str) -> fdo::Result<Direction> {
+    Direction::from_str(direction, true).map_err(fdo::Error::InvalidArgs)
+}
+
+#[interface(name = "org.gridpointer.Daemon")]
+impl DaemonInterface {
+    /// Move `cells` cells towards `direction` (up, down, left or right)
+    #[zbus(name = "Move")]
+    async fn move_cells(&self, direction: &str, cells: u32) -> fdo::Result<()> {
+        let direction = parse_direction(direction)?;
+        self.request(Request::Move { direction, cells }).await?;
+        Ok(())
+    }
+
+    async fn dash(&self, direction: &str) -> fdo::Result<()> {
+        let direction = parse_direction(direction)?;
+        self.request(Request::Dash { direction }).await?;
+        Ok(())
+    }
+
+    async fn jump(&self, col: u32, row: u32) -> fdo::Result<()> {
+        self.request(Request::Jump { col, row }).await?;
+        Ok(())
+    }
+
+    /// Click `button` (left, right or middle)
+    async fn click(&self, button: &str) -> fdo::Result<()> {
+        let button = Button::from_str(button, true).map_err(fdo::Error::InvalidArgs)?;
+        self.request(Request::Click { button }).await?;
+        Ok(())
+    }
+
+    async fn scroll(&self, x: i32, y: i32) -> fdo::Result<()> {
+        self.request(Request::Scroll { x, y }).await?;
+        Ok(())
+    }
+
+    /// Turn keyboard and gamepad control on or off, returning the new state
+    async fn toggle(&self) -> fdo::Result<bool> {
+        let response = self.request(Request::Toggle).await?;
+        Ok(response.data.and_then(|data| data["active"].as_bool()) == Some(true))
+    }
+
+    async fn pause(&self) -> fdo::Result<()> {
+        self.request(Request::Pause).await?;
+        Ok(())
+    }
+
+    async fn resume(&self) -> fdo::Result<()> {
+        self.request(Request::Resume).await?;
+        Ok(())
+    }
+
+    async fn reload(&self) -> fdo::Result<()> {
+        self.request(Request::Reload).await?;
+        Ok(())
+    }
+
+    /// Switch to configuration profile `name`, or back to the base configuration if empty
+    async fn set_profile(&self, name: &str) -> fdo::Result<()> {
+        let name = (!name.is_empty()).then(|| name.to_string());
+        self.request(Request::Profile { name }).await?;
+        Ok(())
+    }
+
+    async fn quit(&self) -> fdo::Result<()> {
+        self.request(Request::Quit).await?;
+        Ok(())
+    }
+
+    /// Current grid cell as (column, row)
+    #[zbus(property)]
+    fn cell(&self) -> (u32, u32) {
+        self.cell
+    }
+
+    /// Name of the active output, empty if unknown
+    #[zbus(property)]
+    fn output(&self) -> String {
+        self.output.clone()
+    }
+
+    /// Whether keyboard and gamepad input drives the grid
+    #[zbus(property)]
+    fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Selected configuration profile, empty for the base configuration
+    #[zbus(property)]
+    fn profile(&self) -> String {
+        self.profile.clone()
+    }
+
+    #[zbus(signal)]
+    async fn config_reloaded(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn device_connected(
+        emitter: &SignalEmitter<'_>,
+        device: &str,
+        name: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn device_disconnected(
+        emitter: &SignalEmitter<'_>,
+        device: &str,
+        name: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// Connect to the session bus and serve the daemon interface until shutdown
+pub async fn run(
+    tx: mpsc::UnboundedSender<Command>,
+    events: broadcast::Sender<Event>,
+    shutdown: broadcast::Receiver<()>,
+) -> anyhow::Result<()> {
+    let connection = Connection::session().await?;
+    serve(connection, tx, events, shutdown).await
+}
+
+/// Export the daemon interface on `connection` and keep it in sync with `events`
+pub async fn serve(
+    connection: Connection,
+    tx: mpsc::UnboundedSender<Command>,
+    events: broadcast::Sender<Event>,
+    mut shutdown: broadcast::Receiver<()>,
+) -> anyhow::Result<()> {
+    // Subscribe before taking the snapshot so no change is missed
+    let mut events = events.subscribe();
+    let status = ipc::dispatch(&tx, Request::Status).await;
+
+    connection
+        .object_server()
+        .at(OBJECT_PATH, DaemonInterface::new(tx, &status))
+        .await?;
+    connection.request_name(BUS_NAME).await?;
+    info!("D-Bus interface available as {}", BUS_NAME);
+
+    let interface = connection
+        .object_server()
+        .interface::<_, DaemonInterface>(OBJECT_PATH)
+        .await?;
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if let Err(e) = publish(&interface, event).await {
+                        warn!("Failed to emit D-Bus signal: {}", e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = shutdown.recv() => {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Update the cached properties for `event` and emit the matching signal
+async fn publish(interface: &InterfaceRef<DaemonInterface>, event: Event) -> zbus::Result<()> {
+    let emitter = interface.signal_emitter();
+    match event {
+        Event::ModeChanged { active } => {
+            let mut daemon = interface.get_mut().await;
+            daemon.active = active;
+            daemon.active_changed(emitter).await
+        }
+        Event::CellChanged { col, row } => {
+            let mut daemon = interface.get_mut().await;
+            daemon.cell = (col, row);
+            daemon.cell_changed(emitter).await
+        }
+        Event::OutputChanged { name } => {
+            let mut daemon = interface.get_mut().await;
+            daemon.output = name.unwrap_or_default();
+            daemon.output_changed(emitter).await
+        }
+        Event::ConfigReloaded => DaemonInterface::config_reloaded(emitter).await,
+        Event::ProfileChanged { name } => {
+            let mut daemon = interface.get_mut().await;
+            daemon.profile = name.unwrap_or_default();
+            daemon.profile_changed(emitter).await
+        }
+        Event::DeviceConnected { device, name } => {
+            DaemonInterface::device_connected(emitter, &device.to_string(), &name).await
+        }
+        Event::DeviceDisconnected { device, name } => {
+            DaemonInterface::device_disconnected(emitter, &device.to_string(), &name).await
+        }
+    }
+}
@@ -13,9 +13,21 @@ pub enum GridPointerError {
     #[error("Configuration error: {0}")]
     Config(String),
 
+    #[error(
+        "Invalid configuration:{}",
+        .0.iter().map(|issue| format!("\n  {}", issue)).collect::<String>()
+    )]
+    InvalidConfig(Vec<crate::config::ConfigIssue>),
+
     #[error("Motion controller error: {0}")]
     Motion(String),
 
+    #[error(
+        "GridPointer is already running{}; stop it or start with --replace",
+        .pid.map(|pid| format!(" (pid {})", pid)).unwrap_or_default()
+    )]
+    AlreadyRunning { pid: Option<u32> },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -0,0 +1,301 @@
+//! Focused-window tracking for per-application profiles
+//!
+//! Follows Hyprland's event socket (`.socket2.sock`) for focus and window
+//! layout events, asking its request socket for the focused window's details
+//! after each. `[[rules]]` in the config map app ids and titles to profiles;
+//! see [`FocusRule`]. The window's geometry backs `grid_target = "window"`.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, info, warn};
+
+/// How long a query made while moving may hold up the cursor
+const QUERY_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Hyprland events after which the focused window, its title or its geometry may have changed
+///
+/// Hyprland has no event for resizes or layout messages such as `togglesplit`;
+/// [`focused_window`] is asked before moving to catch those.
+const WINDOW_EVENTS: &[&str] = &[
+    "activewindow",
+    "windowtitle",
+    "windowtitlev2",
+    "movewindow",
+    "movewindowv2",
+    "changefloatingmode",
+    "fullscreen",
+    "minimized",
+    "pin",
+    "openwindow",
+    "closewindow",
+    "togglegroup",
+    "moveintogroup",
+    "moveoutofgroup",
+    "moveworkspace",
+    "moveworkspacev2",
+    "monitoradded",
+    "monitoraddedv2",
+    "monitorremoved",
+    "configreloaded",
+];
+
+/// The window with keyboard focus
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FocusedWindow {
+    /// Wayland app id, called the window class by Hyprland
+    pub app_id: String,
+    pub title: String,
+    /// Where the window is in the output layout, if known
+    pub geometry: Option<Geometry>,
+}
+
+/// A rectangle in logical pixels of the output layout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Geometry {
+    /// Whether the point is inside the rectangle
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+}
+
+/// Use `profile` while a matching window has focus
+///
+/// `app_id` and `title` are globs where `*` matches any run of characters
+/// and `?` any single one. A rule needs at least one of them, and matches
+/// when all given ones do.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FocusRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub profile: String,
+}
+
+impl FocusRule {
+    pub fn matches(&self, window: &FocusedWindow) -> bool {
+        let matches = |pattern: &Option<String>, text: &str| {
+            pattern
+                .as_deref()
+                .is_none_or(|pattern| glob_match(pattern, text))
+        };
+        (self.app_id.is_some() || self.title.is_some())
+            && matches(&self.app_id, &window.app_id)
+            && matches(&self.title, &window.title)
+    }
+}
+
+/// Profile of the first rule matching `window`
+pub fn profile_for<'a>(rules: &'a [FocusRule], window: &FocusedWindow) -> Option<&'a str> {
+    rules
+        .iter()
+        .find(|rule| rule.matches(window))
+        .map(|rule| rule.profile.as_str())
+}
+
+/// Whether `text` matches `pattern` with `*` and `?` wildcards
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Last `*` seen and the text position it is currently matched up to
+    let mut star = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // Let the last `*` swallow one more character
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Directory holding Hyprland's sockets for this session, if running under Hyprland
+pub fn hyprland_socket_dir() -> Option<PathBuf> {
+    let signature = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE")?;
+    // Hyprland moved its sockets from /tmp to the runtime directory in 0.40
+    let candidates = [
+        dirs::runtime_dir().map(|dir| dir.join("hypr")),
+        Some("/tmp/hypr".into()),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join(&signature))
+        .find(|dir| dir.join(".socket2.sock").exists())
+}
+
+/// Ask for the focused window now, to catch changes no event reports
+///
+/// `None` when not running under a supported compositor, or the query
+/// fails or takes too long; callers keep what they knew before.
+pub async fn focused_window() -> Option<FocusedWindow> {
+    hyprland_focused_window(&hyprland_socket_dir()?).await
+}
+
+/// [`focused_window`] through the Hyprland sockets in `dir`
+pub async fn hyprland_focused_window(dir: &Path) -> Option<FocusedWindow> {
+    match tokio::time::timeout(QUERY_TIMEOUT, active_window(dir)).await {
+        Ok(Ok(window)) => Some(window),
+        Ok(Err(e)) => {
+            debug!("Failed to query the focused window: {}", e);
+            None
+        }
+        Err(_) => {
+            debug!("Hyprland took too long to report the focused window");
+            None
+        }
+    }
+}
+
+/// Send the focused window to `tx` on every focus change until shutdown
+///
+/// Returns right away when not running under a supported compositor.
+pub async fn run(
+    tx: mpsc::UnboundedSender<FocusedWindow>,
+    shutdown: broadcast::Receiver<()>,
+) -> anyhow::Result<()> {
+    match hyprland_socket_dir() {
+        Some(dir) => run_hyprland(&dir, tx, shutdown).await,
+        None => {
+            warn!(
+                "No window focus backend available (only Hyprland is supported): \
+                 [[rules]] and grid_target = \"window\" have no effect"
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Follow focus changes through the Hyprland sockets in `dir`
+pub async fn run_hyprland(
+    dir: &Path,
+    tx: mpsc::UnboundedSender<FocusedWindow>,
+    mut shutdown: broadcast::Receiver<()>,
+) -> anyhow::Result<()> {
+    // Connect to the events first so no change is missed
+    let events = UnixStream::connect(dir.join(".socket2.sock")).await?;
+    let mut events = BufReader::new(events).lines();
+    info!("Following window focus through Hyprland");
+
+    let mut last = None;
+    match active_window(dir).await {
+        Ok(window) => {
+            let _ = tx.send(window.clone());
+            last = Some(window);
+        }
+        Err(e) => warn!("Failed to query the focused window: {}", e),
+    }
+
+    loop {
+        tokio::select! {
+            line = events.next_line() => {
+                let Some(line) = line? else {
+                    warn!("Hyprland closed its event socket, window rules are inactive");
+                    break;
+                };
+                let name = line.split_once(">>").map_or(line.as_str(), |(name, _)| name);
+                if !WINDOW_EVENTS.contains(&name) {
+                    continue;
+                }
+                // The event alone lacks the geometry, and only some events name the window
+                let window = match active_window(dir).await {
+                    Ok(window) => window,
+                    Err(e) => match parse_event(&line) {
+                        Some(window) => {
+                            debug!("Failed to query the focused window: {}", e);
+                            window
+                        }
+                        None => continue,
+                    },
+                };
+                if last.as_ref() == Some(&window) {
+                    continue;
+                }
+                debug!("Focused {:?}", window);
+                if tx.send(window.clone()).is_err() {
+                    break;
+                }
+                last = Some(window);
+            }
+            _ = shutdown.recv() => {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Ask Hyprland's request socket in `dir` for the focused window
+async fn active_window(dir: &Path) -> anyhow::Result<FocusedWindow> {
+    let mut socket = UnixStream::connect(dir.join(".socket.sock")).await?;
+    socket.write_all(b"j/activewindow").await?;
+    let mut reply = String::new();
+    socket.read_to_string(&mut reply).await?;
+
+    // An empty object when nothing has focus
+    let reply: serde_json::Value = serde_json::from_str(&reply)?;
+    let field = |name: &str| reply[name].as_str().unwrap_or_default().to_string();
+    let pair = |name: &str| {
+        let pair = reply[name].as_array()?;
+        let value = |i: usize| pair.get(i)?.as_i64().and_then(|v| i32::try_from(v).ok());
+        Some((value(0)?, value(1)?))
+    };
+    let geometry = match (pair("at"), pair("size")) {
+        (Some((x, y)), Some((width, height))) => Some(Geometry {
+            x,
+            y,
+            width,
+            height,
+        }),
+        _ => None,
+    };
+    Ok(FocusedWindow {
+        app_id: field("class"),
+        title: field("title"),
+        geometry,
+    })
+}
+
+/// Focused window from an `activewindow>>class,title` event line
+pub fn parse_event(line: &str) -> Option<FocusedWindow> {
+    let data = line.strip_prefix("activewindow>>")?;
+    // Titles may contain commas, classes don't
+    let (app_id, title) = data.split_once(',').unwrap_or((data, ""));
+    Some(FocusedWindow {
+        app_id: app_id.to_string(),
+        title: title.to_string(),
+        geometry: None,
+    })
+}
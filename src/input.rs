@@ -1,17 +1,24 @@
 //! Input handling for keyboard and gamepad devices
 
-use crate::config::Config;
+use crate::config::{Config, ConfigChange};
 use crate::error::{GridPointerError, Result};
-use evdev::{Device, EventType, InputEventTrait, Key};
+use crate::motion::{JumpTarget, MotionEvent};
+use clap::ValueEnum;
+use evdev::{Device, EventStream, EventType, InputEventTrait, Key};
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::{RwLock, broadcast, mpsc};
-use tokio::time::{Duration, interval};
+use tokio::time::{Duration, sleep_until};
 use tracing::{debug, info, warn};
 
 /// Direction for movement
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
 pub enum Direction {
     Up,
     Down,
@@ -20,33 +27,351 @@ pub enum Direction {
 }
 
 /// Input events from keyboard or gamepad
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum InputEvent {
-    Move { direction: Direction, dash: bool },
+    Motion {
+        motion: MotionEvent,
+        operator: Option<Operator>,
+    },
+    SetMark {
+        key: char,
+    },
+    JumpToMark {
+        key: char,
+    },
+    HistoryBack,
+    HistoryForward,
+    /// Keys typed so far for an incomplete command, `None` once it completes or is cancelled
+    Pending {
+        keys: Option<String>,
+    },
     Click,
     Quit,
+    DeviceConnected {
+        device: DeviceKind,
+        name: String,
+    },
+    DeviceDisconnected {
+        device: DeviceKind,
+        name: String,
+    },
+}
+
+/// Kinds of input device the daemon reads from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceKind {
+    Keyboard,
+    Gamepad,
+}
+
+impl std::fmt::Display for DeviceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DeviceKind::Keyboard => "keyboard",
+            DeviceKind::Gamepad => "gamepad",
+        })
+    }
+}
+
+/// Operators applied around a motion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    /// Hold the left button from the current cell to the end of the motion
+    Drag,
+    /// Left click once the motion completes
+    Click,
+}
+
+/// Key waiting for a mark name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MarkPrefix {
+    Set,
+    Jump,
+}
+
+/// Vim-style key grammar: `[count][operator][count]motion`, plus mark prefixes
+///
+/// A count before a direction moves that many cells (`5l`). Absolute jumps
+/// take the count as a one-based position instead: `N|` goes to column N and
+/// `NG` to row N, while a bare `|` and `G` go to the start of the row and the
+/// last row. Counts before and after the operator multiply, so `2d3l` drags
+/// six cells to the right. Incomplete commands are dropped after `timeout`
+/// without a key.
+#[derive(Debug, Clone)]
+pub struct KeyParser {
+    timeout: Duration,
+    count: Option<u32>,
+    operator: Option<Operator>,
+    operator_count: Option<u32>,
+    mark: Option<MarkPrefix>,
+    last_key: Option<Instant>,
+}
+
+impl KeyParser {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            count: None,
+            operator: None,
+            operator_count: None,
+            mark: None,
+            last_key: None,
+        }
+    }
+
+    /// Feed a key press, returning an event once a command is complete
+    pub fn feed(&mut self, key: Key, shift: bool, now: Instant) -> Option<InputEvent> {
+        self.expire(now);
+        self.last_key = Some(now);
+
+        if let Some(prefix) = self.mark.take() {
+            self.reset();
+            return match key_to_char(key) {
+                Some(c) if prefix == MarkPrefix::Set => Some(InputEvent::SetMark { key: c }),
+                Some(c) => Some(InputEvent::JumpToMark { key: c }),
+                None => None,
+            };
+        }
+
+        // Counts; a leading zero is a motion of its own
+        if let Some(digit) = key_to_char(key).and_then(|c| c.to_digit(10)) {
+            let slot = match self.operator {
+                Some(_) => &mut self.operator_count,
+                None => &mut self.count,
+            };
+            if digit != 0 || slot.is_some() {
+                *slot = Some(slot.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                return None;
+            }
+        }
+
+        let count = match (self.count, self.operator_count) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(1).saturating_mul(b.unwrap_or(1))),
+        };
+
+        if let Some(motion) = motion_for_key(key, shift, count) {
+            let operator = self.operator;
+            self.reset();
+            return Some(InputEvent::Motion { motion, operator });
+        }
+
+        let operator = match key {
+            Key::KEY_D => Some(Operator::Drag),
+            Key::KEY_C => Some(Operator::Click),
+            _ => None,
+        };
+        if operator.is_some() && self.operator.is_none() {
+            self.operator = operator;
+            return None;
+        }
+
+        // Anything else completes or cancels the pending command
+        let pending = self.pending().is_some();
+        self.reset();
+        match key {
+            Key::KEY_M if !pending => self.mark = Some(MarkPrefix::Set),
+            Key::KEY_APOSTROPHE if !pending => self.mark = Some(MarkPrefix::Jump),
+            Key::KEY_LEFTBRACE | Key::KEY_BACK if !pending => return Some(InputEvent::HistoryBack),
+            Key::KEY_RIGHTBRACE | Key::KEY_FORWARD if !pending => {
+                return Some(InputEvent::HistoryForward);
+            }
+            Key::KEY_SPACE if !pending => return Some(InputEvent::Click),
+            Key::KEY_ESC if !pending => return Some(InputEvent::Quit),
+            _ if pending => debug!("Cancelled pending command"),
+            _ => {}
+        }
+
+        None
+    }
+
+    /// When the incomplete command, if any, times out
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending()?;
+        self.last_key.map(|last| last + self.timeout)
+    }
+
+    /// Drop an incomplete command that has timed out, returning whether one was dropped
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let expired = self.pending().is_some()
+            && self
+                .last_key
+                .is_some_and(|last| now.duration_since(last) >= self.timeout);
+        if expired {
+            debug!("Pending command timed out");
+            self.reset();
+        }
+        expired
+    }
+
+    /// Keys typed so far for an incomplete command
+    pub fn pending(&self) -> Option<String> {
+        let mut keys = String::new();
+        if let Some(count) = self.count {
+            keys.push_str(&count.to_string());
+        }
+        match self.operator {
+            Some(Operator::Drag) => keys.push('d'),
+            Some(Operator::Click) => keys.push('c'),
+            None => {}
+        }
+        if let Some(count) = self.operator_count {
+            keys.push_str(&count.to_string());
+        }
+        match self.mark {
+            Some(MarkPrefix::Set) => keys.push('m'),
+            Some(MarkPrefix::Jump) => keys.push('\''),
+            None => {}
+        }
+
+        (!keys.is_empty()).then_some(keys)
+    }
+
+    fn reset(&mut self) {
+        self.count = None;
+        self.operator = None;
+        self.operator_count = None;
+        self.mark = None;
+    }
+}
+
+/// Motion for a key, given the count typed before it
+fn motion_for_key(key: Key, shift: bool, count: Option<u32>) -> Option<MotionEvent> {
+    let direction = match key {
+        Key::KEY_UP | Key::KEY_K => Some(Direction::Up),
+        Key::KEY_DOWN | Key::KEY_J => Some(Direction::Down),
+        Key::KEY_LEFT | Key::KEY_H => Some(Direction::Left),
+        Key::KEY_RIGHT | Key::KEY_L => Some(Direction::Right),
+        _ => None,
+    };
+    if let Some(direction) = direction {
+        return Some(match count {
+            Some(cells) => MotionEvent::MoveBy { direction, cells },
+            None if shift => MotionEvent::Dash { direction },
+            None => MotionEvent::Move { direction },
+        });
+    }
+
+    // `N|` and `NG` go to column and row N, counting from one
+    let target = match key {
+        Key::KEY_BACKSLASH => match count {
+            Some(col) => JumpTarget::Column(col.saturating_sub(1)),
+            None => JumpTarget::RowStart,
+        },
+        Key::KEY_G => match count {
+            Some(row) => JumpTarget::Row(row.saturating_sub(1)),
+            None => JumpTarget::ColumnEnd,
+        },
+        Key::KEY_0 | Key::KEY_HOME => JumpTarget::RowStart,
+        Key::KEY_END => JumpTarget::RowEnd,
+        Key::KEY_PAGEUP => JumpTarget::ColumnStart,
+        Key::KEY_PAGEDOWN => JumpTarget::ColumnEnd,
+        Key::KEY_KP5 => JumpTarget::Center,
+        Key::KEY_KP7 => JumpTarget::TopLeft,
+        Key::KEY_KP9 => JumpTarget::TopRight,
+        Key::KEY_KP1 => JumpTarget::BottomLeft,
+        Key::KEY_KP3 => JumpTarget::BottomRight,
+        _ => return None,
+    };
+    Some(MotionEvent::Jump { target })
+}
+
+/// Directory input device nodes appear in
+const INPUT_DIR: &str = "/dev/input";
+
+/// When a missing device is looked for again
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reconnect {
+    /// Configured device path, reopened whenever it may have come back
+    pub path: Option<PathBuf>,
+    /// Whether auto-detection found a device before, so it is detected again once lost
+    pub detected: bool,
+}
+
+impl Reconnect {
+    /// Policy for a device configured at `path` (`None` to auto-detect), `found` or not
+    pub fn new(path: Option<PathBuf>, found: bool) -> Self {
+        let detected = found && path.is_none();
+        Self { path, detected }
+    }
+
+    /// Whether to watch for the device coming back while it is not `connected`
+    ///
+    /// An auto-detected device that was never found is not waited for.
+    pub fn armed(&self, connected: bool) -> bool {
+        !connected && (self.path.is_some() || self.detected)
+    }
+}
+
+/// Watches a directory for device nodes being added or made accessible
+pub struct Hotplug {
+    _watcher: RecommendedWatcher,
+    rx: mpsc::UnboundedReceiver<()>,
+}
+
+impl Hotplug {
+    pub fn watch(dir: &Path) -> notify::Result<Self> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut watcher: RecommendedWatcher = notify::Watcher::new(
+            move |result: notify::Result<Event>| {
+                if let Ok(event) = result {
+                    // udev sets permissions after creating the node, so both can matter
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(ModifyKind::Metadata(_))
+                    ) {
+                        let _ = tx.send(());
+                    }
+                }
+            },
+            notify::Config::default(),
+        )?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(Self {
+            _watcher: watcher,
+            rx,
+        })
+    }
+
+    /// Wait for the next change, folding in any that arrived with it
+    pub async fn changed(&mut self) {
+        if self.rx.recv().await.is_none() {
+            return std::future::pending().await;
+        }
+        while self.rx.try_recv().is_ok() {}
+    }
 }
 
 /// Input device manager
 pub struct InputManager {
-    keyboard_device: Option<Device>,
-    gamepad_device: Option<Device>,
+    config: Arc<RwLock<Config>>,
+    keyboard_device: Option<EventStream>,
+    gamepad_device: Option<EventStream>,
+    keyboard_retry: Reconnect,
+    gamepad_retry: Reconnect,
+    /// Watch on /dev/input, kept only while a device worth retrying is missing
+    hotplug: Option<Hotplug>,
     key_states: HashMap<Key, bool>,
+    parser: KeyParser,
+    pending_keys: Option<String>,
 }
 
 impl InputManager {
-    pub async fn new(config: &Arc<RwLock<Config>>) -> anyhow::Result<Self> {
-        let config = config.read().await;
+    pub async fn new(shared: &Arc<RwLock<Config>>) -> anyhow::Result<Self> {
+        let config = shared.read().await;
 
         let keyboard_device = match &config.input.keyboard_device {
             Some(path) => Some(Self::open_device(path)?),
             None => Self::find_keyboard_device()?,
         };
+        let keyboard_device = keyboard_device.map(Self::event_stream).transpose()?;
 
         let gamepad_device = match &config.input.gamepad_device {
             Some(path) => Some(Self::open_device(path)?),
             None => Self::find_gamepad_device()?,
         };
+        let gamepad_device = gamepad_device.map(Self::event_stream).transpose()?;
 
         if keyboard_device.is_none() && gamepad_device.is_none() {
             return Err(
@@ -62,27 +387,76 @@ impl InputManager {
             info!("  Gamepad: enabled");
         }
 
+        let keyboard_retry = Reconnect::new(
+            config.input.keyboard_device.clone().map(PathBuf::from),
+            keyboard_device.is_some(),
+        );
+        let gamepad_retry = Reconnect::new(
+            config.input.gamepad_device.clone().map(PathBuf::from),
+            gamepad_device.is_some(),
+        );
+
         Ok(Self {
+            config: shared.clone(),
             keyboard_device,
             gamepad_device,
+            keyboard_retry,
+            gamepad_retry,
+            hotplug: None,
             key_states: HashMap::new(),
+            parser: KeyParser::new(Duration::from_millis(config.input.pending_timeout_ms)),
+            pending_keys: None,
         })
     }
 
-    /// Main input loop
+    /// Main input loop, woken by device events rather than polling
+    ///
+    /// Device and timeout settings are re-applied when `reloads` reports them changed.
     pub async fn run(
         mut self,
         tx: mpsc::UnboundedSender<InputEvent>,
+        mut reloads: broadcast::Receiver<Vec<ConfigChange>>,
         mut shutdown: broadcast::Receiver<()>,
     ) -> anyhow::Result<()> {
-        let mut poll_timer = interval(Duration::from_millis(1));
+        for (kind, device) in [
+            (DeviceKind::Keyboard, &self.keyboard_device),
+            (DeviceKind::Gamepad, &self.gamepad_device),
+        ] {
+            if let Some(device) = device {
+                let _ = tx.send(InputEvent::DeviceConnected {
+                    device: kind,
+                    name: device_name(device.device()),
+                });
+            }
+        }
 
         loop {
             tokio::select! {
-                _ = poll_timer.tick() => {
-                    if let Err(e) = self.poll_events(&tx).await {
-                        warn!("Input polling error: {}", e);
+                event = next_event(&mut self.keyboard_device) => match event {
+                    Ok(event) => self.handle_device_event(DeviceKind::Keyboard, event, &tx),
+                    Err(e) => {
+                        Self::disconnect(&mut self.keyboard_device, DeviceKind::Keyboard, e, &tx);
+                        // Keys held when the keyboard went away will never be released
+                        self.key_states.clear();
+                        self.update_hotplug();
                     }
+                },
+                event = next_event(&mut self.gamepad_device) => match event {
+                    Ok(event) => self.handle_device_event(DeviceKind::Gamepad, event, &tx),
+                    Err(e) => {
+                        Self::disconnect(&mut self.gamepad_device, DeviceKind::Gamepad, e, &tx);
+                        self.update_hotplug();
+                    }
+                },
+                _ = expiry(self.parser.deadline()) => {
+                    self.parser.expire(Instant::now());
+                    self.sync_pending(&tx);
+                }
+                _ = hotplug_change(&mut self.hotplug) => {
+                    self.reconnect(&tx);
+                }
+                Ok(changes) = reloads.recv() => {
+                    self.apply_config_changes(&changes, &tx).await;
                 }
                 _ = shutdown.recv() => {
                     break;
@@ -93,30 +467,150 @@ impl InputManager {
         Ok(())
     }
 
-    async fn poll_events(&mut self, tx: &mpsc::UnboundedSender<InputEvent>) -> Result<()> {
-        // Poll keyboard
-        if let Some(device) = &mut self.keyboard_device {
-            while let Ok(events) = device.fetch_events() {
-                for event in events {
-                    if let Some(input_event) = self.handle_keyboard_event(event)? {
-                        let _ = tx.send(input_event);
-                    }
-                }
+    fn handle_device_event(
+        &mut self,
+        kind: DeviceKind,
+        event: evdev::InputEvent,
+        tx: &mpsc::UnboundedSender<InputEvent>,
+    ) {
+        let result = match kind {
+            DeviceKind::Keyboard => self.handle_keyboard_event(event),
+            DeviceKind::Gamepad => self.handle_gamepad_event(event),
+        };
+        match result {
+            Ok(Some(input_event)) => {
+                let _ = tx.send(input_event);
             }
+            Ok(None) => {}
+            Err(e) => warn!("Input event error: {}", e),
         }
+        self.sync_pending(tx);
+    }
 
-        // Poll gamepad
-        if let Some(device) = &mut self.gamepad_device {
-            while let Ok(events) = device.fetch_events() {
-                for event in events {
-                    if let Some(input_event) = self.handle_gamepad_event(event)? {
-                        let _ = tx.send(input_event);
-                    }
+    /// Switch to newly configured devices and pending-command timeout
+    async fn apply_config_changes(
+        &mut self,
+        changes: &[ConfigChange],
+        tx: &mpsc::UnboundedSender<InputEvent>,
+    ) {
+        let config = self.config.clone();
+        let config = config.read().await;
+
+        if changes.contains(&ConfigChange::PendingTimeout) {
+            self.parser = KeyParser::new(Duration::from_millis(config.input.pending_timeout_ms));
+            self.sync_pending(tx);
+        }
+        // Open the new devices right away; later they are only retried as usual
+        if changes.contains(&ConfigChange::KeyboardDevice) {
+            let path = config.input.keyboard_device.clone().map(PathBuf::from);
+            Self::release(&mut self.keyboard_device, DeviceKind::Keyboard, tx);
+            self.key_states.clear();
+            self.keyboard_device = Self::reopen(
+                path.as_deref(),
+                Self::find_keyboard_device,
+                DeviceKind::Keyboard,
+                tx,
+            );
+            self.keyboard_retry = Reconnect::new(path, self.keyboard_device.is_some());
+        }
+        if changes.contains(&ConfigChange::GamepadDevice) {
+            let path = config.input.gamepad_device.clone().map(PathBuf::from);
+            Self::release(&mut self.gamepad_device, DeviceKind::Gamepad, tx);
+            self.gamepad_device = Self::reopen(
+                path.as_deref(),
+                Self::find_gamepad_device,
+                DeviceKind::Gamepad,
+                tx,
+            );
+            self.gamepad_retry = Reconnect::new(path, self.gamepad_device.is_some());
+        }
+        self.update_hotplug();
+    }
+
+    /// Close a device that is no longer configured
+    fn release(
+        slot: &mut Option<EventStream>,
+        kind: DeviceKind,
+        tx: &mpsc::UnboundedSender<InputEvent>,
+    ) {
+        if let Some(device) = slot.take() {
+            let name = device_name(device.device());
+            info!(
+                "Input device {} ({}) released after config change",
+                name, kind
+            );
+            let _ = tx.send(InputEvent::DeviceDisconnected { device: kind, name });
+        }
+    }
+
+    /// Drop a device that failed to read, which means it has gone away
+    fn disconnect(
+        slot: &mut Option<EventStream>,
+        kind: DeviceKind,
+        error: std::io::Error,
+        tx: &mpsc::UnboundedSender<InputEvent>,
+    ) {
+        if let Some(device) = slot.take() {
+            let name = device_name(device.device());
+            warn!("Input device {} ({}) disconnected: {}", name, kind, error);
+            let _ = tx.send(InputEvent::DeviceDisconnected { device: kind, name });
+        }
+    }
+
+    /// Try to open devices that are missing, announcing any that turn up
+    fn reconnect(&mut self, tx: &mpsc::UnboundedSender<InputEvent>) {
+        if self.keyboard_retry.armed(self.keyboard_device.is_some()) {
+            self.keyboard_device = Self::reopen(
+                self.keyboard_retry.path.as_deref(),
+                Self::find_keyboard_device,
+                DeviceKind::Keyboard,
+                tx,
+            );
+        }
+        if self.gamepad_retry.armed(self.gamepad_device.is_some()) {
+            self.gamepad_device = Self::reopen(
+                self.gamepad_retry.path.as_deref(),
+                Self::find_gamepad_device,
+                DeviceKind::Gamepad,
+                tx,
+            );
+        }
+        self.update_hotplug();
+    }
+
+    /// Watch /dev/input only while a device worth retrying is missing
+    fn update_hotplug(&mut self) {
+        let armed = self.keyboard_retry.armed(self.keyboard_device.is_some())
+            || self.gamepad_retry.armed(self.gamepad_device.is_some());
+        if !armed {
+            self.hotplug = None;
+        } else if self.hotplug.is_none() {
+            match Hotplug::watch(Path::new(INPUT_DIR)) {
+                Ok(hotplug) => {
+                    debug!("Watching {} for devices to reconnect", INPUT_DIR);
+                    self.hotplug = Some(hotplug);
                 }
+                Err(e) => warn!("Cannot watch {} for devices: {}", INPUT_DIR, e),
             }
         }
+    }
 
-        Ok(())
+    fn reopen(
+        path: Option<&Path>,
+        find: fn() -> Result<Option<Device>>,
+        kind: DeviceKind,
+        tx: &mpsc::UnboundedSender<InputEvent>,
+    ) -> Option<EventStream> {
+        let device = match path {
+            Some(path) => Self::open_device(path).ok(),
+            None => find().ok().flatten(),
+        }?;
+        let device = Self::event_stream(device).ok()?;
+
+        let name = device_name(device.device());
+        info!("Input device {} ({}) connected", name, kind);
+        let _ = tx.send(InputEvent::DeviceConnected { device: kind, name });
+        Some(device)
     }
 
     fn handle_keyboard_event(&mut self, event: evdev::InputEvent) -> Result<Option<InputEvent>> {
@@ -133,51 +627,24 @@ impl InputManager {
             self.key_states.insert(key, pressed);
         }
 
-        if pressed {
-            match key {
-                Key::KEY_UP => {
-                    let dash = self.key_states.get(&Key::KEY_LEFTSHIFT).unwrap_or(&false)
-                        || self.key_states.get(&Key::KEY_RIGHTSHIFT).unwrap_or(&false);
-                    return Ok(Some(InputEvent::Move {
-                        direction: Direction::Up,
-                        dash,
-                    }));
-                }
-                Key::KEY_DOWN => {
-                    let dash = self.key_states.get(&Key::KEY_LEFTSHIFT).unwrap_or(&false)
-                        || self.key_states.get(&Key::KEY_RIGHTSHIFT).unwrap_or(&false);
-                    return Ok(Some(InputEvent::Move {
-                        direction: Direction::Down,
-                        dash,
-                    }));
-                }
-                Key::KEY_LEFT => {
-                    let dash = self.key_states.get(&Key::KEY_LEFTSHIFT).unwrap_or(&false)
-                        || self.key_states.get(&Key::KEY_RIGHTSHIFT).unwrap_or(&false);
-                    return Ok(Some(InputEvent::Move {
-                        direction: Direction::Left,
-                        dash,
-                    }));
-                }
-                Key::KEY_RIGHT => {
-                    let dash = self.key_states.get(&Key::KEY_LEFTSHIFT).unwrap_or(&false)
-                        || self.key_states.get(&Key::KEY_RIGHTSHIFT).unwrap_or(&false);
-                    return Ok(Some(InputEvent::Move {
-                        direction: Direction::Right,
-                        dash,
-                    }));
-                }
-                Key::KEY_SPACE => {
-                    return Ok(Some(InputEvent::Click));
-                }
-                Key::KEY_ESC => {
-                    return Ok(Some(InputEvent::Quit));
-                }
-                _ => {}
-            }
+        // Modifiers don't interrupt pending commands
+        if !pressed || is_modifier(key) {
+            return Ok(None);
         }
 
-        Ok(None)
+        let shift = [Key::KEY_LEFTSHIFT, Key::KEY_RIGHTSHIFT]
+            .iter()
+            .any(|key| self.key_states.get(key).copied().unwrap_or(false));
+        Ok(self.parser.feed(key, shift, Instant::now()))
+    }
+
+    /// Report changes to the pending command
+    fn sync_pending(&mut self, tx: &mpsc::UnboundedSender<InputEvent>) {
+        let keys = self.parser.pending();
+        if keys != self.pending_keys {
+            self.pending_keys = keys.clone();
+            let _ = tx.send(InputEvent::Pending { keys });
+        }
     }
 
     fn handle_gamepad_event(&mut self, event: evdev::InputEvent) -> Result<Option<InputEvent>> {
@@ -203,6 +670,13 @@ impl InputManager {
             .map_err(|e| GridPointerError::Input(format!("Failed to open device: {}", e)))
     }
 
+    /// Read `device` asynchronously, waking only when it has events
+    fn event_stream(device: Device) -> Result<EventStream> {
+        device
+            .into_event_stream()
+            .map_err(|e| GridPointerError::Input(format!("Failed to watch device: {}", e)))
+    }
+
     fn find_keyboard_device() -> Result<Option<Device>> {
         for path in evdev::enumerate() {
             if let Ok(device) = Device::open(&path.1) {
@@ -231,3 +705,89 @@ impl InputManager {
         Ok(None)
     }
 }
+
+/// Next event from the device in `slot`; never completes without a device
+async fn next_event(slot: &mut Option<EventStream>) -> std::io::Result<evdev::InputEvent> {
+    match slot {
+        Some(device) => device.next_event().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Next change to /dev/input; never completes without a watch
+async fn hotplug_change(hotplug: &mut Option<Hotplug>) {
+    match hotplug {
+        Some(hotplug) => hotplug.changed().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Sleep until `deadline`; never completes without one
+async fn expiry(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Name reported by the device, for logs and status
+fn device_name(device: &Device) -> String {
+    device.name().unwrap_or("unknown").to_string()
+}
+
+/// Whether `key` is a modifier that may be held while typing a follow-up key
+fn is_modifier(key: Key) -> bool {
+    matches!(
+        key,
+        Key::KEY_LEFTSHIFT
+            | Key::KEY_RIGHTSHIFT
+            | Key::KEY_LEFTCTRL
+            | Key::KEY_RIGHTCTRL
+            | Key::KEY_LEFTALT
+            | Key::KEY_RIGHTALT
+    )
+}
+
+/// Character for letter and digit keys, used to name marks
+fn key_to_char(key: Key) -> Option<char> {
+    const KEYS: [(Key, char); 36] = [
+        (Key::KEY_A, 'a'),
+        (Key::KEY_B, 'b'),
+        (Key::KEY_C, 'c'),
+        (Key::KEY_D, 'd'),
+        (Key::KEY_E, 'e'),
+        (Key::KEY_F, 'f'),
+        (Key::KEY_G, 'g'),
+        (Key::KEY_H, 'h'),
+        (Key::KEY_I, 'i'),
+        (Key::KEY_J, 'j'),
+        (Key::KEY_K, 'k'),
+        (Key::KEY_L, 'l'),
+        (Key::KEY_M, 'm'),
+        (Key::KEY_N, 'n'),
+        (Key::KEY_O, 'o'),
+        (Key::KEY_P, 'p'),
+        (Key::KEY_Q, 'q'),
+        (Key::KEY_R, 'r'),
+        (Key::KEY_S, 's'),
+        (Key::KEY_T, 't'),
+        (Key::KEY_U, 'u'),
+        (Key::KEY_V, 'v'),
+        (Key::KEY_W, 'w'),
+        (Key::KEY_X, 'x'),
+        (Key::KEY_Y, 'y'),
+        (Key::KEY_Z, 'z'),
+        (Key::KEY_0, '0'),
+        (Key::KEY_1, '1'),
+        (Key::KEY_2, '2'),
+        (Key::KEY_3, '3'),
+        (Key::KEY_4, '4'),
+        (Key::KEY_5, '5'),
+        (Key::KEY_6, '6'),
+        (Key::KEY_7, '7'),
+        (Key::KEY_8, '8'),
+        (Key::KEY_9, '9'),
+    ];
+
+    KEYS.iter().find(|(k, _)| *k == key).map(|(_, c)| *c)
+}
@@ -0,0 +1,311 @@
+//! Unix-domain control socket for scripting a running daemon
+//!
+//! Clients write one JSON request per line and read one JSON response per
+//! line back, e.g. `{"command": "move", "direction": "left", "cells": 3}`.
+//! After a `subscribe` request the connection streams [`Event`]s instead.
+
+use crate::input::{DeviceKind, Direction};
+use crate::wl::Button;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::OwnedReadHalf;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tracing::{debug, info, warn};
+
+/// Commands and queries accepted on the control socket
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    /// Move a number of cells in a direction
+    Move {
+        direction: Direction,
+        #[serde(default = "default_cells")]
+        cells: u32,
+    },
+    /// Dash in a direction
+    Dash { direction: Direction },
+    /// Move to an absolute cell
+    Jump { col: u32, row: u32 },
+    /// Click a mouse button at the current position
+    Click {
+        #[serde(default)]
+        button: Button,
+    },
+    /// Scroll by wheel steps, positive values scroll down and right
+    Scroll {
+        #[serde(default)]
+        x: i32,
+        #[serde(default)]
+        y: i32,
+    },
+    /// Turn keyboard and gamepad control on or off
+    Toggle,
+    /// Turn keyboard and gamepad control off
+    Pause,
+    /// Turn keyboard and gamepad control back on
+    Resume,
+    /// Re-read the configuration file
+    Reload,
+    /// Switch to a configuration profile, or back to the base configuration without one
+    Profile {
+        #[serde(default)]
+        name: Option<String>,
+    },
+    /// Stop the daemon
+    Quit,
+    /// Query the current grid cell
+    Cell,
+    /// Query the normalized screen position
+    Position,
+    /// Query the active output
+    Output,
+    /// Query the active configuration
+    Config,
+    /// Query everything above except the configuration
+    Status,
+    /// Answer with the status, then stream state-change events
+    Subscribe,
+}
+
+fn default_cells() -> u32 {
+    1
+}
+
+/// Reply to a single request
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+            data: None,
+        }
+    }
+
+    pub fn data(data: serde_json::Value) -> Self {
+        Self {
+            ok: true,
+            error: None,
+            data: Some(data),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(message.into()),
+            data: None,
+        }
+    }
+}
+
+/// State changes streamed to subscribed clients, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    ModeChanged { active: bool },
+    CellChanged { col: u32, row: u32 },
+    OutputChanged { name: Option<String> },
+    ConfigReloaded,
+    ProfileChanged { name: Option<String> },
+    DeviceConnected { device: DeviceKind, name: String },
+    DeviceDisconnected { device: DeviceKind, name: String },
+}
+
+/// A request forwarded to the main loop, with a channel for its response
+pub type Command = (Request, oneshot::Sender<Response>);
+
+/// Listener for the control socket
+///
+/// The socket file is removed again when the server is dropped.
+pub struct IpcServer {
+    listener: UnixListener,
+    /// Socket file to remove on drop, if this server created it
+    path: Option<PathBuf>,
+}
+
+impl IpcServer {
+    /// Default socket location, `$XDG_RUNTIME_DIR/gridpointer.sock`
+    pub fn default_path() -> anyhow::Result<PathBuf> {
+        let mut path = dirs::runtime_dir().context("XDG_RUNTIME_DIR is not set")?;
+        path.push("gridpointer.sock");
+        Ok(path)
+    }
+
+    /// Bind the control socket at `path`, replacing a stale socket file
+    pub async fn bind(path: PathBuf) -> anyhow::Result<Self> {
+        if path.exists() {
+            if UnixStream::connect(&path).await.is_ok() {
+                anyhow::bail!("Another instance is listening on {}", path.display());
+            }
+            std::fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("Failed to bind control socket {}", path.display()))?;
+        info!("Control socket listening on {}", path.display());
+
+        Ok(Self {
+            listener,
+            path: Some(path),
+        })
+    }
+
+    /// Serve on a socket that is already listening, e.g. one passed in by systemd
+    ///
+    /// The socket file is left in place on drop since it belongs to its creator.
+    pub fn from_std(listener: std::os::unix::net::UnixListener) -> anyhow::Result<Self> {
+        listener.set_nonblocking(true)?;
+        let listener = UnixListener::from_std(listener)?;
+        if let Some(path) = listener.local_addr()?.as_pathname() {
+            info!("Control socket listening on {} (activated)", path.display());
+        }
+
+        Ok(Self {
+            listener,
+            path: None,
+        })
+    }
+
+    /// Accept clients and forward their requests to `tx` until shutdown
+    ///
+    /// Subscribed clients receive everything published on `events`.
+    pub async fn run(
+        self,
+        tx: mpsc::UnboundedSender<Command>,
+        events: broadcast::Sender<Event>,
+        mut shutdown: broadcast::Receiver<()>,
+    ) -> anyhow::Result<()> {
+        loop {
+            tokio::select! {
+                result = self.listener.accept() => match result {
+                    Ok((stream, _)) => {
+                        let tx = tx.clone();
+                        let events = events.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = Self::serve(stream, tx, events).await {
+                                debug!("Control client error: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => warn!("Control socket accept error: {}", e),
+                },
+                _ = shutdown.recv() => {
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn serve(
+        stream: UnixStream,
+        tx: mpsc::UnboundedSender<Command>,
+        events: broadcast::Sender<Event>,
+    ) -> anyhow::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(Request::Subscribe) => {
+                    // Subscribe before taking the snapshot so no change is missed
+                    let mut events = events.subscribe();
+                    let snapshot = dispatch(&tx, Request::Status).await;
+                    write_line(&mut writer, &snapshot).await?;
+
+                    loop {
+                        match events.recv().await {
+                            Ok(event) => write_line(&mut writer, &event).await?,
+                            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                                debug!("Subscriber lagged, dropped {} events", missed);
+                            }
+                            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                        }
+                    }
+                }
+                Ok(request) => dispatch(&tx, request).await,
+                Err(e) => Response::error(format!("Invalid request: {}", e)),
+            };
+
+            write_line(&mut writer, &response).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Hand a request to the main loop and wait for its response
+pub async fn dispatch(tx: &mpsc::UnboundedSender<Command>, request: Request) -> Response {
+    debug!("Control request: {:?}", request);
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if tx.send((request, reply_tx)).is_err() {
+        return Response::error("Daemon is shutting down");
+    }
+    reply_rx
+        .await
+        .unwrap_or_else(|_| Response::error("Daemon is shutting down"))
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+async fn write_line<W, T>(writer: &mut W, value: &T) -> anyhow::Result<()>
+where
+    W: AsyncWriteExt + Unpin,
+    T: Serialize,
+{
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    Ok(())
+}
+
+/// Send a single request to the daemon listening on `path` and wait for the response
+pub async fn send_request(path: &Path, request: &Request) -> anyhow::Result<Response> {
+    let stream = UnixStream::connect(path)
+        .await
+        .with_context(|| format!("Failed to connect to {}", path.display()))?;
+    let (reader, mut writer) = stream.into_split();
+    write_line(&mut writer, request).await?;
+
+    let reply = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .context("Daemon closed the connection without responding")?;
+    Ok(serde_json::from_str(&reply)?)
+}
+
+/// Subscribe to the daemon listening on `path`
+///
+/// The first line is the status response and every later line an [`Event`].
+pub async fn subscribe(path: &Path) -> anyhow::Result<Lines<BufReader<OwnedReadHalf>>> {
+    let stream = UnixStream::connect(path)
+        .await
+        .with_context(|| format!("Failed to connect to {}", path.display()))?;
+    let (reader, mut writer) = stream.into_split();
+    write_line(&mut writer, &Request::Subscribe).await?;
+    Ok(BufReader::new(reader).lines())
+}
@@ -0,0 +1,271 @@
+//! Included files and named profiles layered into one configuration
+//!
+//! A file can pull in others with a top-level `include = ["base.toml"]`,
+//! resolved relative to the including file, and define `[profile.<name>]`
+//! tables shaped like the rest of the config. Layers apply bottom to top:
+//! included files in order, the including file, the selected profile, then
+//! overrides from the environment and `--set`. Tables merge key by key, any
+//! other value is replaced.
+
+use crate::config::{Config, ConfigIssue, ConfigOverride};
+use crate::error::{GridPointerError, Result};
+use crate::migrate;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Environment variable selecting the profile at startup
+pub const PROFILE_VAR: &str = "GRIDPOINTER_PROFILE";
+
+/// One file contributing to the configuration
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub path: PathBuf,
+    /// Text as in the file, for locating problems
+    pub content: String,
+    table: toml::Table,
+}
+
+/// Every file making up a configuration, merged, with profiles kept apart
+#[derive(Debug, Clone)]
+pub struct Layers {
+    /// Included files first, the including file last
+    sources: Vec<Source>,
+    base: toml::Table,
+    profiles: BTreeMap<String, toml::Table>,
+}
+
+impl Layers {
+    /// Read everything included by the file at `path`, whose text is `content`
+    pub fn load(path: &Path, content: &str) -> Result<Self> {
+        let mut layers = Self {
+            sources: Vec::new(),
+            base: toml::Table::new(),
+            profiles: BTreeMap::new(),
+        };
+        let mut base = toml::Table::new();
+        layers.read(path, content.to_string(), None, &mut Vec::new(), &mut base)?;
+
+        if let Some(profiles) = base.remove("profile") {
+            let toml::Value::Table(profiles) = profiles else {
+                return Err(config_error("profile must be a table of profiles"));
+            };
+            for (name, profile) in profiles {
+                let toml::Value::Table(profile) = profile else {
+                    return Err(config_error(format!("profile.{} must be a table", name)));
+                };
+                // Rules pick profiles, so they only make sense in the base
+                if profile.contains_key("rules") {
+                    return Err(config_error(format!(
+                        "profile.{}: rules can only be set outside profiles",
+                        name
+                    )));
+                }
+                // Checked on their own, as the merged result can't tell where a bad value came from
+                profile
+                    .clone()
+                    .try_into::<Config>()
+                    .map_err(|e| config_error(format!("profile.{}: {}", name, e.message())))?;
+                layers.profiles.insert(name, profile);
+            }
+        }
+        layers.base = base;
+        Ok(layers)
+    }
+
+    /// Read the file at `path` and its includes into `merged`
+    ///
+    /// `upgraded` is the text to use instead of `content` if it was upgraded in memory.
+    fn read(
+        &mut self,
+        path: &Path,
+        content: String,
+        upgraded: Option<String>,
+        stack: &mut Vec<PathBuf>,
+        merged: &mut toml::Table,
+    ) -> Result<()> {
+        let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if stack.contains(&canonical) {
+            return Err(config_error(format!(
+                "{} is included by itself",
+                path.display()
+            )));
+        }
+        // Errors in the including file are already reported against its path
+        let included = !stack.is_empty();
+        let in_file = |e: toml::de::Error| match included {
+            true => config_error(format!("{}: {}", path.display(), e)),
+            false => GridPointerError::from(e),
+        };
+
+        let text = upgraded.as_deref().unwrap_or(&content);
+        let mut table: toml::Table = toml::from_str(text).map_err(in_file)?;
+        // Type errors are reported at their location in this file
+        toml::from_str::<Config>(text).map_err(in_file)?;
+
+        let includes = match table.remove("include") {
+            None => Vec::new(),
+            Some(includes) => includes
+                .try_into::<Vec<String>>()
+                .map_err(|_| config_error("include must be a list of paths"))?,
+        };
+        stack.push(canonical);
+        for include in includes {
+            let include = resolve(path, &include);
+            let text = std::fs::read_to_string(&include).map_err(|e| {
+                config_error(format!("Cannot include {}: {}", include.display(), e))
+            })?;
+            // Shared files may lag behind; they are upgraded in memory only
+            let upgraded = migrate::upgrade(&text)
+                .map_err(|e| config_error(format!("{}: {}", include.display(), e)))?
+                .map(|upgrade| upgrade.content);
+            self.read(&include, text, upgraded, stack, merged)?;
+        }
+        stack.pop();
+
+        let source_table = table.clone();
+        merge(merged, table);
+        self.sources.push(Source {
+            path: path.to_path_buf(),
+            content,
+            table: source_table,
+        });
+        Ok(())
+    }
+
+    /// The files read, included ones first
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    /// Names of the profiles defined across all files
+    pub fn profiles(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    /// The configuration with `profile` and then `overrides` applied, validated
+    ///
+    /// Problems are located in the file that set the value, or blamed on the override.
+    pub fn build(&self, profile: Option<&str>, overrides: &[ConfigOverride]) -> Result<Config> {
+        let mut table = self.base.clone();
+        let profile = match profile {
+            Some(name) => {
+                let layer = self.profiles.get(name).ok_or_else(|| {
+                    let defined: Vec<_> = self.profiles().collect();
+                    config_error(format!(
+                        "No profile named {}, defined are: {}",
+                        name,
+                        defined.join(", ")
+                    ))
+                })?;
+                merge(&mut table, layer.clone());
+                Some((name, layer))
+            }
+            None => None,
+        };
+        for setting in overrides {
+            setting.apply(&mut table);
+        }
+        let config: Config = table.try_into().map_err(|e: toml::de::Error| {
+            config_error(format!("Invalid override: {}", e.message()))
+        })?;
+
+        let mut issues = config.validate();
+        for (i, rule) in config.rules.iter().enumerate() {
+            if !self.profiles.contains_key(&rule.profile) {
+                issues.push(ConfigIssue::new(
+                    &format!("rules.{}.profile", i),
+                    format!("no profile named {}", rule.profile),
+                ));
+            }
+        }
+        if issues.is_empty() {
+            return Ok(config);
+        }
+        for issue in &mut issues {
+            if let Some(setting) = overrides.iter().rev().find(|s| s.covers(&issue.key)) {
+                issue.message = format!("{} (set by {})", issue.message, setting.source);
+                continue;
+            }
+            if let Some((name, _)) = profile.filter(|(_, layer)| defines(layer, &issue.key)) {
+                issue.key = format!("profile.{}.{}", name, issue.key);
+            }
+            self.locate(issue);
+        }
+        Err(GridPointerError::InvalidConfig(issues))
+    }
+
+    /// Keys that don't match a setting, with the file each is in
+    pub fn unknown_keys(&self) -> Vec<(&Path, ConfigIssue)> {
+        self.sources
+            .iter()
+            .flat_map(|source| {
+                Config::unknown_keys(&source.content)
+                    .into_iter()
+                    .map(|issue| (source.path.as_path(), issue))
+            })
+            .collect()
+    }
+
+    /// Locate `issue` in the last file that sets its key, else in the including file
+    fn locate(&self, issue: &mut ConfigIssue) {
+        let Some(source) = self
+            .sources
+            .iter()
+            .rev()
+            .find(|source| defines(&source.table, &issue.key))
+            .or(self.sources.last())
+        else {
+            return;
+        };
+        if let Ok(document) = toml_edit::ImDocument::parse(source.content.as_str()) {
+            issue.locate(&source.content, document.as_item());
+        }
+        if !self.is_including(source) {
+            issue.message = format!("{} (in {})", issue.message, source.path.display());
+        }
+    }
+
+    fn is_including(&self, source: &Source) -> bool {
+        self.sources
+            .last()
+            .is_some_and(|last| std::ptr::eq(last, source))
+    }
+}
+
+/// Merge `top` into `base`, key by key for tables
+pub fn merge(base: &mut toml::Table, top: toml::Table) {
+    for (key, value) in top {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(top)) => merge(base, top),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Whether `table` has a value at the dotted `key`
+fn defines(table: &toml::Table, key: &str) -> bool {
+    let mut value = None;
+    let mut table = Some(table);
+    for part in key.split('.') {
+        value = table.and_then(|table| table.get(part));
+        table = value.and_then(toml::Value::as_table);
+    }
+    value.is_some()
+}
+
+/// `include` relative to the file that names it, with `~/` for the home directory
+fn resolve(from: &Path, include: &str) -> PathBuf {
+    if let (Some(rest), Some(home)) = (include.strip_prefix("~/"), dirs::home_dir()) {
+        return home.join(rest);
+    }
+    match from.parent() {
+        Some(dir) => dir.join(include),
+        None => PathBuf::from(include),
+    }
+}
+
+fn config_error(message: impl Into<String>) -> GridPointerError {
+    GridPointerError::Config(message.into())
+}
@@ -3,17 +3,31 @@
 //! This module provides a clean API for embedding GridPointer functionality
 //! into other applications or for creating custom implementations.
 
+pub mod cli;
 pub mod config;
+#[cfg(feature = "dbus")]
+pub mod dbus;
 pub mod error;
+pub mod focus;
 pub mod input;
+pub mod ipc;
+pub mod layers;
+pub mod lock;
+pub mod marks;
+pub mod migrate;
 pub mod motion;
+pub mod signals;
+pub mod systemd;
 pub mod wl;
 
 pub use config::{Config, ConfigManager};
 pub use error::{GridPointerError, Result};
-pub use input::{Direction, InputEvent, InputManager};
-pub use motion::{MotionController, MotionEvent};
-pub use wl::WaylandManager;
+pub use input::{Direction, InputEvent, InputManager, KeyParser, Operator};
+pub use ipc::{IpcServer, Request, Response};
+pub use lock::InstanceLock;
+pub use marks::{Mark, MarkStore};
+pub use motion::{Clock, JumpTarget, ManualClock, MotionController, MotionEvent, SystemClock};
+pub use wl::{Button, WaylandManager};
 
 /// Version information
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
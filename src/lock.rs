@@ -0,0 +1,101 @@
+//! Single-instance lock held for the lifetime of the daemon
+
+use crate::error::{GridPointerError, Result};
+use crate::ipc::{self, Request};
+use anyhow::Context;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info};
+
+/// How long a replaced instance gets to shut down
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An exclusive `flock` on a file holding the daemon's PID
+///
+/// The lock is released when this is dropped or the process exits.
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Default lock location, `$XDG_RUNTIME_DIR/gridpointer.lock`
+    pub fn default_path() -> anyhow::Result<PathBuf> {
+        let mut path = dirs::runtime_dir().context("XDG_RUNTIME_DIR is not set")?;
+        path.push("gridpointer.lock");
+        Ok(path)
+    }
+
+    /// Take the lock at `path`, or fail with the PID of the instance holding it
+    pub fn acquire(path: PathBuf) -> Result<Self> {
+        // Not truncated on open: the file belongs to the holder until we have the lock
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(GridPointerError::AlreadyRunning {
+                    pid: read_pid(&mut file),
+                });
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        writeln!(file, "{}", std::process::id())?;
+        file.flush()?;
+        debug!("Acquired instance lock {}", path.display());
+
+        Ok(Self { _file: file })
+    }
+
+    /// Ask the instance holding the lock at `path` to quit, then take the lock
+    ///
+    /// The running instance is stopped through its control socket at `socket`.
+    pub async fn replace(path: PathBuf, socket: &Path) -> anyhow::Result<Self> {
+        let pid = match Self::acquire(path.clone()) {
+            Err(GridPointerError::AlreadyRunning { pid }) => pid,
+            result => return Ok(result?),
+        };
+
+        info!(
+            "Replacing running instance{}",
+            pid.map(|pid| format!(" (pid {})", pid)).unwrap_or_default()
+        );
+        let response = ipc::send_request(socket, &Request::Quit)
+            .await
+            .context("Could not ask the running instance to quit")?;
+        if !response.ok {
+            anyhow::bail!(
+                "Running instance refused to quit: {}",
+                response.error.unwrap_or_default()
+            );
+        }
+
+        let deadline = tokio::time::Instant::now() + REPLACE_TIMEOUT;
+        loop {
+            match Self::acquire(path.clone()) {
+                Err(GridPointerError::AlreadyRunning { .. })
+                    if tokio::time::Instant::now() < deadline =>
+                {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+}
+
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut content = String::new();
+    file.read_to_string(&mut content).ok()?;
+    content.trim().parse().ok()
+}
@@ -3,72 +3,231 @@
 //! A high-performance daemon that provides smooth, game-like cursor movement
 //! on a logical grid with configurable easing and dash support.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::io::IsTerminal;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use std::sync::Arc;
 use tokio::sync::{broadcast, mpsc};
-use tokio::time::{Duration, interval};
-use tracing::{error, info, warn};
+use tokio::time::{Duration, Interval, MissedTickBehavior, interval};
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::EnvFilter;
 
+mod cli;
 mod config;
+#[cfg(feature = "dbus")]
+mod dbus;
 mod error;
+mod focus;
 mod input;
+mod ipc;
+mod layers;
+mod lock;
+mod marks;
+mod migrate;
 mod motion;
+mod signals;
+mod systemd;
 mod wl;
 
-use crate::config::{Config, ConfigManager};
-use crate::input::{InputEvent, InputManager};
-use crate::motion::{MotionController, MotionEvent};
+use crate::cli::{Cli, Command, DaemonArgs};
+use crate::config::{Config, ConfigChange, ConfigManager, ConfigOverride, GridTarget};
+use crate::focus::FocusedWindow;
+use crate::input::{InputEvent, InputManager, Operator};
+use crate::ipc::{Event, IpcServer, Request, Response};
+use crate::layers::Layers;
+use crate::lock::InstanceLock;
+use crate::marks::{Mark, MarkStore};
+use crate::motion::{MotionController, MotionEvent, Region};
+use crate::signals::Signals;
 use crate::wl::WaylandManager;
+use serde_json::json;
 
 /// Main application state
 pub struct GridPointer {
-    config_manager: ConfigManager,
-    input_manager: InputManager,
+    config_manager: Arc<ConfigManager>,
+    input_manager: Option<InputManager>,
     motion_controller: MotionController,
     wayland_manager: WaylandManager,
+    marks: MarkStore,
+    after_motion: Option<Operator>,
+    /// Whether keyboard and gamepad input drives the grid
+    active: bool,
+    pending_keys: Option<String>,
+    /// Control socket, taken when the main loop starts
+    ipc_server: Option<IpcServer>,
+    /// State changes for control socket subscribers
+    events: broadcast::Sender<Event>,
+    last_cell: (u32, u32),
+    last_output: Option<String>,
+    /// Window with keyboard focus, for the window rules and `grid_target = "window"`
+    focused_window: Option<FocusedWindow>,
+    /// Held until the daemon exits so only one instance runs
+    _lock: InstanceLock,
 }
 
 impl GridPointer {
     /// Initialize the GridPointer daemon
-    pub async fn new() -> Result<Self> {
+    ///
+    /// `socket_path` overrides the default control socket location.
+    pub async fn new(args: &DaemonArgs, socket_path: Option<PathBuf>) -> Result<Self> {
         info!("Initializing GridPointer daemon");
 
-        let config_manager = ConfigManager::new().await?;
+        let lock_path = InstanceLock::default_path()?;
+        let lock = if args.replace {
+            let socket = match &socket_path {
+                Some(path) => path.clone(),
+                None => IpcServer::default_path()?,
+            };
+            InstanceLock::replace(lock_path, &socket).await?
+        } else {
+            InstanceLock::acquire(lock_path)?
+        };
+
+        // Environment overrides first so `--set` wins
+        let mut overrides = ConfigOverride::from_env();
+        overrides.extend(args.overrides.iter().cloned());
+        let profile = args.profile.clone().or_else(|| {
+            std::env::var(crate::layers::PROFILE_VAR)
+                .ok()
+                .filter(|profile| !profile.is_empty())
+        });
+        let config_path = ConfigManager::resolve_path(args.config.clone())?;
+        let config_manager = ConfigManager::open(config_path, profile, overrides).await?;
         let config = config_manager.get_config();
 
-        let wayland_manager = WaylandManager::new().await?;
+        let mut wayland_manager = WaylandManager::new().await?;
+        Self::focus_target_monitor(
+            &mut wayland_manager,
+            &config.read().await.display.target_monitor,
+        );
         let input_manager = InputManager::new(&config).await?;
-        let motion_controller = MotionController::new(config.clone());
+        let mut motion_controller = MotionController::new(config.clone());
+
+        let (width, height) = wayland_manager.get_primary_output_size();
+        motion_controller.set_output_size(width as u32, height as u32);
+
+        let marks = MarkStore::load(MarkStore::default_path()?, &config.read().await.marks).await;
+        let last_cell = motion_controller.grid_position();
+        let last_output = wayland_manager.active_output_name();
+        let ipc_server = Self::control_socket(socket_path).await;
 
-        Ok(Self {
-            config_manager,
-            input_manager,
+        let daemon = Self {
+            config_manager: Arc::new(config_manager),
+            input_manager: Some(input_manager),
             motion_controller,
             wayland_manager,
-        })
+            marks,
+            after_motion: None,
+            active: true,
+            pending_keys: None,
+            ipc_server,
+            events: broadcast::channel(64).0,
+            last_cell,
+            last_output,
+            focused_window: None,
+            _lock: lock,
+        };
+        systemd::ready(&daemon.status_line());
+
+        Ok(daemon)
     }
 
-    /// Main event loop running at 360 Hz
+    /// Open the control socket, preferring one passed in by socket activation
+    ///
+    /// The daemon keeps running without it.
+    async fn control_socket(path: Option<PathBuf>) -> Option<IpcServer> {
+        let server = match systemd::listen_fds().into_iter().next() {
+            Some(fd) => IpcServer::from_std(fd.into()),
+            None => match path.map_or_else(IpcServer::default_path, Ok) {
+                Ok(path) => IpcServer::bind(path).await,
+                Err(e) => Err(e),
+            },
+        };
+
+        match server {
+            Ok(server) => Some(server),
+            Err(e) => {
+                warn!("Control socket disabled: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Main event loop, ticking only while the cursor is moving
     pub async fn run(mut self) -> Result<()> {
-        info!("Starting GridPointer main loop at 360 Hz");
+        info!("Starting GridPointer main loop");
 
         let (shutdown_tx, mut shutdown_rx) = broadcast::channel(1);
         let (input_tx, mut input_rx) = mpsc::unbounded_channel();
         let (motion_tx, mut motion_rx) = mpsc::unbounded_channel();
+        let (ipc_tx, mut ipc_rx) = mpsc::unbounded_channel();
 
         // Start input handling
+        let input_manager = self
+            .input_manager
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Input manager already running"))?;
+        let input_reloads = self.config_manager.subscribe_reloads();
         let input_shutdown = shutdown_tx.subscribe();
-        let input_handle =
-            tokio::spawn(async move { self.input_manager.run(input_tx, input_shutdown).await });
+        let input_handle = tokio::spawn(async move {
+            input_manager
+                .run(input_tx, input_reloads, input_shutdown)
+                .await
+        });
+
+        // Shut down, reload and pause on signals
+        let signals = Signals::new()?;
+        let signals_tx = ipc_tx.clone();
+        let signals_shutdown = shutdown_tx.subscribe();
+        let signals_handle =
+            tokio::spawn(async move { signals.run(signals_tx, signals_shutdown).await });
 
         // Start config hot-reload
+        let config_manager = self.config_manager.clone();
         let config_shutdown = shutdown_tx.subscribe();
         let config_handle =
-            tokio::spawn(async move { self.config_manager.watch_config(config_shutdown).await });
+            tokio::spawn(async move { config_manager.watch_config(config_shutdown).await });
+
+        // Follow window focus for the window rules
+        let (focus_tx, mut focus_rx) = mpsc::unbounded_channel();
+        let focus_shutdown = shutdown_tx.subscribe();
+        let focus_handle = tokio::spawn(async move {
+            if let Err(e) = focus::run(focus_tx, focus_shutdown).await {
+                warn!("Window rules disabled: {}", e);
+            }
+        });
+
+        // Serve the control socket
+        let ipc_handle = self.ipc_server.take().map(|server| {
+            let ipc_shutdown = shutdown_tx.subscribe();
+            let ipc_tx = ipc_tx.clone();
+            let events = self.events.clone();
+            tokio::spawn(async move { server.run(ipc_tx, events, ipc_shutdown).await })
+        });
+
+        // Mirror the control socket on the session bus; optional like the socket
+        #[cfg(feature = "dbus")]
+        let dbus_handle = {
+            let dbus_tx = ipc_tx.clone();
+            let events = self.events.clone();
+            let dbus_shutdown = shutdown_tx.subscribe();
+            tokio::spawn(async move {
+                if let Err(e) = dbus::run(dbus_tx, events, dbus_shutdown).await {
+                    warn!("D-Bus interface disabled: {}", e);
+                }
+            })
+        };
+
+        let mut config_reloads = self.config_manager.subscribe_reloads();
+
+        // Started by motion and stopped again once it has finished
+        let mut update_timer: Option<Interval> = None;
 
-        // Main update loop at 360 Hz (≈2.78ms per frame)
-        let mut update_timer = interval(Duration::from_micros(2778));
-        update_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        // Pinged from this loop so a hung loop gets the daemon restarted
+        let mut watchdog = systemd::watchdog_interval().map(interval);
 
         loop {
             tokio::select! {
@@ -79,6 +238,33 @@ impl GridPointer {
                     }
                 }
 
+                // Handle control socket requests
+                Some((request, reply)) = ipc_rx.recv() => {
+                    let response = self.handle_request(request, &motion_tx, &shutdown_tx).await;
+                    let _ = reply.send(response);
+                }
+
+                // Switch profiles for the focused window, and follow it with the grid
+                Some(window) = focus_rx.recv() => {
+                    self.focused_window = Some(window);
+                    self.apply_window_rules().await;
+                    let target = self.config_manager.get_config().read().await.display.grid_target;
+                    if self.apply_grid_target(target) {
+                        self.motion_controller.reposition();
+                        self.publish_changes();
+                    }
+                }
+
+                // Re-apply changed settings and tell subscribers about config reloads
+                Ok(changes) = config_reloads.recv() => {
+                    self.apply_config_changes(&changes).await;
+                    if changes.contains(&ConfigChange::UpdateRate) {
+                        // Restarted at the new rate below if still moving
+                        update_timer = None;
+                    }
+                    let _ = self.events.send(Event::ConfigReloaded);
+                }
+
                 // Handle motion updates
                 Some(motion_event) = motion_rx.recv() => {
                     if let Err(e) = self.handle_motion_event(motion_event).await {
@@ -86,13 +272,21 @@ impl GridPointer {
                     }
                 }
 
-                // 360 Hz update tick
-                _ = update_timer.tick() => {
+                // Update tick while moving
+                _ = tick(&mut update_timer) => {
                     if let Some(position) = self.motion_controller.update() {
                         if let Err(e) = self.wayland_manager.move_cursor(position.0, position.1).await {
                             warn!("Cursor move error: {}", e);
                         }
                     }
+                    if let Err(e) = self.finish_operator().await {
+                        error!("Operator error: {}", e);
+                    }
+                }
+
+                // systemd watchdog, when the service has one
+                _ = tick(&mut watchdog) => {
+                    systemd::watchdog();
                 }
 
                 // Shutdown signal
@@ -101,11 +295,33 @@ impl GridPointer {
                     break;
                 }
             }
+
+            // Nothing to update while idle, so the daemon sleeps until the next event
+            let moving = !self.motion_controller.is_idle() || self.after_motion.is_some();
+            if moving && update_timer.is_none() {
+                update_timer = Some(self.update_timer().await);
+            } else if !moving {
+                update_timer = None;
+            }
         }
 
         // Cleanup
+        systemd::stopping();
+        if self.after_motion.take() == Some(Operator::Drag) {
+            // Don't leave the button held when stopped mid-drag
+            if let Err(e) = self.wayland_manager.release_left().await {
+                warn!("Failed to release drag: {}", e);
+            }
+        }
         let _ = input_handle.await;
+        let _ = signals_handle.await;
         let _ = config_handle.await;
+        let _ = focus_handle.await;
+        if let Some(handle) = ipc_handle {
+            let _ = handle.await;
+        }
+        #[cfg(feature = "dbus")]
+        let _ = dbus_handle.await;
 
         info!("GridPointer daemon stopped");
         Ok(())
@@ -117,14 +333,75 @@ impl GridPointer {
         motion_tx: &mpsc::UnboundedSender<MotionEvent>,
         shutdown_tx: &broadcast::Sender<()>,
     ) -> Result<()> {
+        // Device changes are reported even while grid mode is off
+        let is_device_event = matches!(
+            event,
+            InputEvent::DeviceConnected { .. } | InputEvent::DeviceDisconnected { .. }
+        );
+        if !self.active && !is_device_event {
+            return Ok(());
+        }
+
         match event {
-            InputEvent::Move { direction, dash } => {
-                let motion_event = if dash {
-                    MotionEvent::Dash { direction }
-                } else {
-                    MotionEvent::Move { direction }
+            InputEvent::Motion {
+                motion,
+                operator: None,
+            } => {
+                let _ = motion_tx.send(motion);
+            }
+            InputEvent::Motion {
+                motion,
+                operator: Some(operator),
+            } => {
+                // Handled directly so the operator is armed against this motion
+                if operator == Operator::Drag {
+                    self.wayland_manager.press_left().await?;
+                }
+                self.handle_motion_event(motion).await?;
+                self.after_motion = Some(operator);
+            }
+            InputEvent::Pending { keys } => {
+                match &keys {
+                    Some(keys) => info!("Pending: {}", keys),
+                    None => debug!("Pending command cleared"),
+                }
+                self.pending_keys = keys;
+            }
+            InputEvent::SetMark { key } => {
+                let (col, row) = self.motion_controller.grid_position();
+                let mark = Mark {
+                    col,
+                    row,
+                    output: self.wayland_manager.active_output_connector(),
                 };
-                let _ = motion_tx.send(motion_event);
+                self.marks.set(key, mark).await?;
+            }
+            InputEvent::JumpToMark { key } => match self.marks.get(key).cloned() {
+                Some(mark) => {
+                    if let Some(output) = &mark.output {
+                        let current = self.wayland_manager.active_output_name();
+                        if self.wayland_manager.focus_output(output) {
+                            let (width, height) = self.wayland_manager.get_primary_output_size();
+                            self.motion_controller
+                                .set_output_size(width as u32, height as u32);
+                            if current.as_deref() != Some(output.as_str()) {
+                                self.motion_controller.set_region(Region::FULL);
+                            }
+                        } else {
+                            warn!("Output {} for mark '{}' not found", output, key);
+                        }
+                    }
+                    let _ = motion_tx.send(MotionEvent::JumpTo {
+                        cell: (mark.col, mark.row),
+                    });
+                }
+                None => warn!("Mark '{}' is not set", key),
+            },
+            InputEvent::HistoryBack => {
+                let _ = motion_tx.send(MotionEvent::Back);
+            }
+            InputEvent::HistoryForward => {
+                let _ = motion_tx.send(MotionEvent::Forward);
             }
             InputEvent::Click => {
                 self.wayland_manager.click_left().await?;
@@ -132,12 +409,527 @@ impl GridPointer {
             InputEvent::Quit => {
                 let _ = shutdown_tx.send(());
             }
+            InputEvent::DeviceConnected { device, name } => {
+                let _ = self.events.send(Event::DeviceConnected { device, name });
+            }
+            InputEvent::DeviceDisconnected { device, name } => {
+                let _ = self.events.send(Event::DeviceDisconnected { device, name });
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer a control socket request
+    async fn handle_request(
+        &mut self,
+        request: Request,
+        motion_tx: &mpsc::UnboundedSender<MotionEvent>,
+        shutdown_tx: &broadcast::Sender<()>,
+    ) -> Response {
+        let motion = match request {
+            Request::Move { direction, cells } => MotionEvent::MoveBy { direction, cells },
+            Request::Dash { direction } => MotionEvent::Dash { direction },
+            Request::Jump { col, row } => MotionEvent::JumpTo { cell: (col, row) },
+            Request::Click { button } => {
+                return match self.wayland_manager.click(button).await {
+                    Ok(()) => Response::ok(),
+                    Err(e) => Response::error(e.to_string()),
+                };
+            }
+            Request::Scroll { x, y } => {
+                return match self.wayland_manager.scroll(x, y).await {
+                    Ok(()) => Response::ok(),
+                    Err(e) => Response::error(e.to_string()),
+                };
+            }
+            Request::Toggle | Request::Pause | Request::Resume => {
+                let active = match request {
+                    Request::Pause => false,
+                    Request::Resume => true,
+                    _ => !self.active,
+                };
+                self.set_active(active);
+                return Response::data(json!({ "active": self.active }));
+            }
+            Request::Reload => {
+                return match self.config_manager.reload().await {
+                    Ok(_) => Response::ok(),
+                    Err(e) => Response::error(format!("{:#}", e)),
+                };
+            }
+            Request::Profile { name } => {
+                return match self.config_manager.set_profile(name).await {
+                    Ok(_) => {
+                        // A window rule may still win over the chosen profile
+                        let name = self.config_manager.profile().await;
+                        let _ = self
+                            .events
+                            .send(Event::ProfileChanged { name: name.clone() });
+                        Response::data(json!({ "profile": name }))
+                    }
+                    Err(e) => Response::error(format!("{:#}", e)),
+                };
+            }
+            Request::Quit => {
+                let _ = shutdown_tx.send(());
+                return Response::ok();
+            }
+            Request::Cell => return Response::data(self.cell_json()),
+            Request::Position => return Response::data(self.position_json()),
+            Request::Output => {
+                return Response::data(
+                    json!({ "name": self.wayland_manager.active_output_name() }),
+                );
+            }
+            Request::Config => {
+                let config = self.config_manager.get_config();
+                let config = config.read().await;
+                return match serde_json::to_value(&*config) {
+                    Ok(value) => Response::data(value),
+                    Err(e) => Response::error(e.to_string()),
+                };
+            }
+            // Subscriptions are served by the control socket, starting with the status
+            Request::Status | Request::Subscribe => {
+                return Response::data(json!({
+                    "active": self.active,
+                    "cell": self.cell_json(),
+                    "position": self.position_json(),
+                    "output": self.wayland_manager.active_output_name(),
+                    "pending": self.pending_keys,
+                    "profile": self.config_manager.profile().await,
+                }));
+            }
+        };
+
+        let _ = motion_tx.send(motion);
+        Response::ok()
+    }
+
+    /// Re-apply display and grid settings changed by a config reload
+    ///
+    /// Input settings are picked up by the input task itself.
+    async fn apply_config_changes(&mut self, changes: &[ConfigChange]) {
+        let config = self.config_manager.get_config();
+        let config = config.read().await;
+
+        if changes.contains(&ConfigChange::TargetMonitor) {
+            Self::focus_target_monitor(&mut self.wayland_manager, &config.display.target_monitor);
+            let (width, height) = self.wayland_manager.get_primary_output_size();
+            self.motion_controller
+                .set_output_size(width as u32, height as u32);
+        }
+        let moved = (changes.contains(&ConfigChange::GridTarget)
+            || changes.contains(&ConfigChange::TargetMonitor))
+            && self.apply_grid_target(config.display.grid_target);
+        if moved || ConfigChange::repositions(changes) {
+            self.motion_controller.reposition();
+        }
+        if changes.contains(&ConfigChange::Marks) {
+            self.marks.set_predefined(&config.marks);
+        }
+
+        drop(config);
+        self.publish_changes();
+        if changes.contains(&ConfigChange::Rules) {
+            self.apply_window_rules().await;
+        }
+    }
+
+    /// Use the profile the window rules pick for the focused window
+    ///
+    /// The settings are re-applied once the resulting changes come back
+    /// through the reload channel.
+    async fn apply_window_rules(&mut self) {
+        let Some(window) = &self.focused_window else {
+            return;
+        };
+        let profile = {
+            let config = self.config_manager.get_config();
+            let config = config.read().await;
+            focus::profile_for(&config.rules, window).map(str::to_string)
+        };
+
+        let before = self.config_manager.profile().await;
+        if let Err(e) = self.config_manager.set_window_profile(profile).await {
+            warn!("Keeping the current profile for {}: {:#}", window.app_id, e);
+            return;
+        }
+        let after = self.config_manager.profile().await;
+        if after != before {
+            let _ = self.events.send(Event::ProfileChanged { name: after });
+        }
+    }
+
+    /// Spread the grid over the focused window or the whole output, per `grid_target`
+    ///
+    /// In window mode the window's output becomes the active one. Returns
+    /// whether the grid moved, so the cursor needs re-placing.
+    fn apply_grid_target(&mut self, target: GridTarget) -> bool {
+        let output = self.wayland_manager.active_output_name();
+        let window = self
+            .focused_window
+            .as_ref()
+            .and_then(|window| window.geometry);
+        let region = match (target, window) {
+            (GridTarget::Window, Some(window)) => {
+                let (x, y) = window.center();
+                if self.wayland_manager.focus_point(x, y) {
+                    let (width, height) = self.wayland_manager.get_primary_output_size();
+                    self.motion_controller
+                        .set_output_size(width as u32, height as u32);
+                }
+                self.wayland_manager
+                    .output_geometry()
+                    .and_then(|output| Region::of(window, output))
+                    .unwrap_or(Region::FULL)
+            }
+            _ => Region::FULL,
+        };
+
+        let output_changed = self.wayland_manager.active_output_name() != output;
+        if region == self.motion_controller.region() && !output_changed {
+            return false;
+        }
+        debug!("Spreading the grid over {:?}", region);
+        self.motion_controller.set_region(region);
+        true
+    }
+
+    /// Pick up resizes of the focused window, which no focus event reports
+    ///
+    /// Only asked in window mode. The query is cut short if the compositor is
+    /// slow, keeping the geometry known from focus events.
+    async fn refresh_window_region(&mut self) {
+        let target = self
+            .config_manager
+            .get_config()
+            .read()
+            .await
+            .display
+            .grid_target;
+        if target != GridTarget::Window {
+            return;
+        }
+        let Some(window) = focus::focused_window().await else {
+            return;
+        };
+        if self.focused_window.as_ref() != Some(&window) {
+            self.focused_window = Some(window);
+            self.apply_grid_target(target);
+        }
+    }
+
+    fn focus_target_monitor(wayland_manager: &mut WaylandManager, target: &str) {
+        if !wayland_manager.focus_target(target) {
+            warn!(
+                "Output {} not found, staying on {}",
+                target,
+                wayland_manager
+                    .active_output_name()
+                    .as_deref()
+                    .unwrap_or("the primary output")
+            );
+        }
+    }
+
+    /// Timer for cursor updates at `display.update_rate_hz`
+    ///
+    /// Defaults to the active output's refresh rate, or 360 Hz if it is unknown.
+    async fn update_timer(&self) -> Interval {
+        let configured = self
+            .config_manager
+            .get_config()
+            .read()
+            .await
+            .display
+            .update_rate_hz
+            .filter(|&hz| hz > 0);
+        let hz = match configured {
+            Some(hz) => hz as f64,
+            None => self.wayland_manager.refresh_rate().unwrap_or(360.0),
+        };
+        debug!("Updating the cursor at {:.0} Hz", hz);
+
+        let mut timer = interval(Duration::from_secs_f64(1.0 / hz));
+        timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        timer
+    }
+
+    /// Turn grid mode on or off, telling subscribers if it changed
+    fn set_active(&mut self, active: bool) {
+        if active == self.active {
+            return;
+        }
+
+        self.active = active;
+        info!("Grid mode {}", if active { "enabled" } else { "disabled" });
+        let _ = self.events.send(Event::ModeChanged { active });
+        systemd::status(&self.status_line());
+    }
+
+    /// One-line summary for `systemctl status`
+    fn status_line(&self) -> String {
+        format!(
+            "Grid mode {} on {}",
+            if self.active { "on" } else { "off" },
+            self.wayland_manager
+                .active_output_name()
+                .as_deref()
+                .unwrap_or("unknown output")
+        )
+    }
+
+    fn cell_json(&self) -> serde_json::Value {
+        let (col, row) = self.motion_controller.grid_position();
+        json!({ "col": col, "row": row })
+    }
+
+    fn position_json(&self) -> serde_json::Value {
+        let (x, y) = self.motion_controller.screen_position();
+        json!({ "x": x, "y": y })
+    }
+
+    /// Complete a pending operator once its motion has finished
+    async fn finish_operator(&mut self) -> Result<()> {
+        if !self.motion_controller.is_idle() {
+            return Ok(());
+        }
+
+        match self.after_motion.take() {
+            Some(Operator::Drag) => self.wayland_manager.release_left().await?,
+            Some(Operator::Click) => self.wayland_manager.click_left().await?,
+            None => {}
         }
         Ok(())
     }
 
     async fn handle_motion_event(&mut self, event: MotionEvent) -> Result<()> {
+        self.refresh_window_region().await;
         self.motion_controller.handle_event(event);
+
+        if let Some(direction) = self.motion_controller.take_handoff() {
+            if self.wayland_manager.switch_output(direction) {
+                let (width, height) = self.wayland_manager.get_primary_output_size();
+                self.motion_controller
+                    .set_output_size(width as u32, height as u32);
+                // A focused window the grid was confined to is left behind
+                self.motion_controller.set_region(Region::FULL);
+                self.motion_controller.enter_output(direction);
+            }
+        }
+
+        self.publish_changes();
         Ok(())
     }
+
+    /// Tell subscribers about a new cell or output
+    fn publish_changes(&mut self) {
+        let output = self.wayland_manager.active_output_name();
+        if output != self.last_output {
+            self.last_output = output.clone();
+            let _ = self.events.send(Event::OutputChanged { name: output });
+            systemd::status(&self.status_line());
+        }
+
+        let (col, row) = self.motion_controller.grid_position();
+        if (col, row) != self.last_cell {
+            self.last_cell = (col, row);
+            let _ = self.events.send(Event::CellChanged { col, row });
+        }
+    }
+}
+
+/// Wait for the next tick of an optional timer; never completes without one
+async fn tick(timer: &mut Option<Interval>) {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    if cli.version {
+        println!("{}", gridpointer::version_string());
+        return Ok(());
+    }
+
+    match cli.command {
+        None => run_daemon(DaemonArgs::default(), cli.socket).await,
+        Some(Command::Daemon(args)) => run_daemon(args, cli.socket).await,
+        Some(command) => run_client(&command, cli.socket).await,
+    }
+}
+
+async fn run_daemon(args: DaemonArgs, socket: Option<PathBuf>) -> Result<()> {
+    if !args.foreground {
+        return detach(&args, socket.as_deref());
+    }
+
+    let filter = match &args.log_level {
+        Some(level) => EnvFilter::try_new(level)?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_ansi(std::io::stdout().is_terminal())
+        .init();
+
+    GridPointer::new(&args, socket).await?.run().await
+}
+
+/// Start the daemon again in the background, logging to the state directory
+fn detach(args: &DaemonArgs, socket: Option<&Path>) -> Result<()> {
+    // Fail here rather than in the background if another instance is running
+    if !args.replace {
+        drop(InstanceLock::acquire(InstanceLock::default_path()?)?);
+    }
+
+    let mut log_path = dirs::state_dir().context("Could not determine state directory")?;
+    log_path.push("gridpointer");
+    std::fs::create_dir_all(&log_path)?;
+    log_path.push("daemon.log");
+    let log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)?;
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    if let Some(socket) = socket {
+        command.arg("--socket").arg(socket);
+    }
+    command.args(["daemon", "--foreground"]);
+    if let Some(config) = &args.config {
+        command.arg("--config").arg(config);
+    }
+    if let Some(profile) = &args.profile {
+        command.arg("--profile").arg(profile);
+    }
+    for setting in &args.overrides {
+        command
+            .arg("--set")
+            .arg(format!("{}={}", setting.key, setting.value));
+    }
+    if let Some(level) = &args.log_level {
+        command.arg("--log-level").arg(level);
+    }
+    if args.replace {
+        command.arg("--replace");
+    }
+
+    // A process group of its own keeps terminal signals away from the daemon
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .process_group(0)
+        .spawn()?;
+
+    println!(
+        "GridPointer started (pid {}), logging to {}",
+        child.id(),
+        log_path.display()
+    );
+    Ok(())
+}
+
+/// Send a client command to the running daemon and print its reply
+async fn run_client(command: &Command, socket: Option<PathBuf>) -> Result<()> {
+    if let Command::Config { file: Some(file) } = command {
+        return print_config_file(file).await;
+    }
+
+    let request = command.request().context("Not a client command")?;
+    let path = match socket {
+        Some(path) => path,
+        None => IpcServer::default_path()?,
+    };
+
+    if let Command::Subscribe = command {
+        let mut lines = ipc::subscribe(&path)
+            .await
+            .context("Could not reach the daemon, is it running?")?;
+        while let Some(line) = lines.next_line().await? {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    let response = ipc::send_request(&path, &request)
+        .await
+        .context("Could not reach the daemon, is it running?")?;
+    if !response.ok {
+        anyhow::bail!(
+            response
+                .error
+                .unwrap_or_else(|| "Request failed".to_string())
+        );
+    }
+
+    match (command, response.data) {
+        (Command::Status { json: true }, Some(data)) => {
+            println!("{}", serde_json::to_string_pretty(&data)?);
+        }
+        (Command::Status { json: false }, Some(data)) => {
+            let active = data["active"] == true;
+            println!("Grid mode: {}", if active { "on" } else { "off" });
+            println!(
+                "Cell:      {}, {}",
+                data["cell"]["col"], data["cell"]["row"]
+            );
+            println!(
+                "Position:  {:.3}, {:.3}",
+                data["position"]["x"].as_f64().unwrap_or_default(),
+                data["position"]["y"].as_f64().unwrap_or_default()
+            );
+            println!(
+                "Output:    {}",
+                data["output"].as_str().unwrap_or("unknown")
+            );
+            if let Some(profile) = data["profile"].as_str() {
+                println!("Profile:   {}", profile);
+            }
+            if let Some(pending) = data["pending"].as_str() {
+                println!("Pending:   {}", pending);
+            }
+        }
+        (Command::Config { .. }, Some(data)) => {
+            let config: Config = serde_json::from_value(data)?;
+            print!("{}", toml::to_string_pretty(&config)?);
+        }
+        (Command::Toggle | Command::Pause | Command::Resume, Some(data)) => {
+            println!(
+                "Grid mode {}",
+                if data["active"] == true { "on" } else { "off" }
+            );
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Print the configuration `path` amounts to, defaults included
+async fn print_config_file(path: &Path) -> Result<()> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let upgrade = migrate::upgrade(&content)?;
+    let content = upgrade.map_or(content, |upgrade| {
+        eprintln!("note: upgraded from version {}", upgrade.from);
+        upgrade.content
+    });
+
+    let layers = Layers::load(path, &content)?;
+    let config = layers.build(None, &[])?;
+    for (file, issue) in layers.unknown_keys() {
+        eprintln!("warning: {}: {}", file.display(), issue);
+    }
+
+    print!("{}", toml::to_string_pretty(&config)?);
+    Ok(())
 }
@@ -0,0 +1,123 @@
+//! Named marks for jumping back to saved grid positions
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+/// A saved grid cell, optionally tied to an output
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Mark {
+    pub col: u32,
+    pub row: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+/// Mark storage persisted to a state file
+///
+/// Marks predefined in the config are used for keys that have not been set
+/// at runtime; a mark set at runtime takes precedence and survives restarts.
+pub struct MarkStore {
+    path: PathBuf,
+    predefined: BTreeMap<char, Mark>,
+    marks: BTreeMap<char, Mark>,
+}
+
+impl MarkStore {
+    /// Load marks from `path`, falling back to `predefined` for unset keys
+    pub async fn load(path: PathBuf, predefined: &BTreeMap<String, Mark>) -> Self {
+        let predefined = Self::parse_predefined(predefined);
+
+        let marks = match Self::read_marks(&path).await {
+            Ok(marks) => marks,
+            Err(e) => {
+                warn!("Failed to load marks from {}: {}", path.display(), e);
+                BTreeMap::new()
+            }
+        };
+
+        debug!("Loaded {} marks from {}", marks.len(), path.display());
+
+        Self {
+            path,
+            predefined,
+            marks,
+        }
+    }
+
+    /// Replace the predefined marks, e.g. after a config reload
+    pub fn set_predefined(&mut self, predefined: &BTreeMap<String, Mark>) {
+        self.predefined = Self::parse_predefined(predefined);
+    }
+
+    fn parse_predefined(predefined: &BTreeMap<String, Mark>) -> BTreeMap<char, Mark> {
+        predefined
+            .iter()
+            .filter_map(|(key, mark)| match Self::parse_key(key) {
+                Some(key) => Some((key, mark.clone())),
+                None => {
+                    warn!("Ignoring mark '{}': names must be a single character", key);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Default state file location under the user's data directory
+    pub fn default_path() -> anyhow::Result<PathBuf> {
+        let mut path = dirs::data_dir().context("Could not determine data directory")?;
+        path.push("gridpointer");
+        path.push("marks.toml");
+        Ok(path)
+    }
+
+    /// Look up a mark, preferring runtime marks over predefined ones
+    pub fn get(&self, key: char) -> Option<&Mark> {
+        self.marks.get(&key).or_else(|| self.predefined.get(&key))
+    }
+
+    /// Store a mark and persist the state file
+    pub async fn set(&mut self, key: char, mark: Mark) -> anyhow::Result<()> {
+        info!("Set mark '{}' at ({}, {})", key, mark.col, mark.row);
+        self.marks.insert(key, mark);
+        self.save().await
+    }
+
+    async fn save(&self) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let marks: BTreeMap<String, &Mark> = self
+            .marks
+            .iter()
+            .map(|(key, mark)| (key.to_string(), mark))
+            .collect();
+        let content = toml::to_string_pretty(&marks)?;
+        tokio::fs::write(&self.path, content).await?;
+        Ok(())
+    }
+
+    async fn read_marks(path: &Path) -> anyhow::Result<BTreeMap<char, Mark>> {
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+
+        let content = tokio::fs::read_to_string(path).await?;
+        let marks: BTreeMap<String, Mark> = toml::from_str(&content)?;
+        Ok(marks
+            .into_iter()
+            .filter_map(|(key, mark)| Some((Self::parse_key(&key)?, mark)))
+            .collect())
+    }
+
+    fn parse_key(key: &str) -> Option<char> {
+        let mut chars = key.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Upgrades older configuration layouts to the current one
+//!
+//! Each migration edits the parsed document in place, so comments and
+//! formatting in the user's file survive. Files without a `version` key
+//! predate versioning and are treated as version 0. A file is only
+//! rewritten when a migration actually edits it.
+
+use crate::error::{GridPointerError, Result};
+use toml_edit::DocumentMut;
+
+/// Layout version written by this build
+pub const CONFIG_VERSION: u32 = 1;
+
+/// One step from `from` to `from + 1`
+struct Migration {
+    from: u32,
+    description: &'static str,
+    /// Edit the document, describing each change made
+    apply: fn(&mut DocumentMut) -> Vec<String>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 0,
+    description: "record the layout version",
+    apply: |_| Vec::new(),
+}];
+
+/// A configuration brought up to [`CONFIG_VERSION`]
+#[derive(Debug, Clone)]
+pub struct Upgrade {
+    /// Version the file was written for
+    pub from: u32,
+    pub content: String,
+    /// What changed, for the log
+    pub changes: Vec<String>,
+}
+
+/// Upgrade `content` to the current layout, or `None` if no migration edits it
+pub fn upgrade(content: &str) -> Result<Option<Upgrade>> {
+    let mut document: DocumentMut = content
+        .parse()
+        .map_err(|e| GridPointerError::Config(format!("{}", e)))?;
+
+    let from = match document.get("version") {
+        None => 0,
+        Some(item) => item
+            .as_integer()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| {
+                GridPointerError::Config("version must be a non-negative integer".to_string())
+            })?,
+    };
+    if from > CONFIG_VERSION {
+        return Err(GridPointerError::Config(format!(
+            "version {} was written by a newer GridPointer, this one reads up to version {}",
+            from, CONFIG_VERSION
+        )));
+    }
+    if from == CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    let mut changes = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= from) {
+        let edits = (migration.apply)(&mut document);
+        if edits.is_empty() {
+            continue;
+        }
+        changes.push(format!(
+            "version {} to {}: {}",
+            migration.from,
+            migration.from + 1,
+            migration.description
+        ));
+        changes.extend(edits);
+    }
+    // The layout is unchanged, so the file reads fine as it is
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    document["version"] = toml_edit::value(CONFIG_VERSION as i64);
+
+    Ok(Some(Upgrade {
+        from,
+        content: document.to_string(),
+        changes,
+    }))
+}
@@ -1,18 +1,147 @@
 //! Movement controller with smooth easing and dash support
 
 use crate::input::Direction;
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tracing::debug;
 
-use crate::config::Config;
+use crate::config::{CellAnchor, Config, DurationMode, EdgeMode, MovementConfig, Overshoot};
+use crate::focus::Geometry;
 
 /// Motion events for the controller
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum MotionEvent {
     Move { direction: Direction },
     Dash { direction: Direction },
+    MoveBy { direction: Direction, cells: u32 },
+    JumpTo { cell: (u32, u32) },
+    Jump { target: JumpTarget },
+    Back,
+    Forward,
+}
+
+/// Well-known grid cells for absolute jumps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JumpTarget {
+    Center,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    /// First column of the current row
+    RowStart,
+    /// Last column of the current row
+    RowEnd,
+    /// First row of the current column
+    ColumnStart,
+    /// Last row of the current column
+    ColumnEnd,
+    /// Zero-based column in the current row
+    Column(u32),
+    /// Zero-based row in the current column
+    Row(u32),
+}
+
+/// Part of the output the grid is spread over, in normalized output coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Region {
+    /// The whole output
+    pub const FULL: Region = Region {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+
+    /// Where `window` is on `output`, clipped to the output's edges
+    ///
+    /// Returns `None` if the window isn't on the output at all.
+    pub fn of(window: Geometry, output: Geometry) -> Option<Region> {
+        let left = window.x.max(output.x);
+        let top = window.y.max(output.y);
+        let right = (window.x + window.width).min(output.x + output.width);
+        let bottom = (window.y + window.height).min(output.y + output.height);
+        if right <= left || bottom <= top {
+            return None;
+        }
+
+        let width = output.width as f64;
+        let height = output.height as f64;
+        Some(Region {
+            x: (left - output.x) as f64 / width,
+            y: (top - output.y) as f64 / height,
+            width: (right - left) as f64 / width,
+            height: (bottom - top) as f64 / height,
+        })
+    }
+}
+
+/// Source of the current time for tweens
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The real monotonic clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to, for tests and replays
+///
+/// Clones share the same time, so a test can keep one and hand another to
+/// the controller.
+#[derive(Debug, Clone)]
+#[allow(dead_code)] // The daemon itself always runs on the system clock
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+#[allow(dead_code)]
+impl ManualClock {
+    /// Start at the current real time
+    pub fn new() -> Self {
+        Self::starting_at(Instant::now())
+    }
+
+    pub fn starting_at(now: Instant) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+
+    pub fn set(&self, now: Instant) {
+        *self.now.lock().unwrap() = now;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
 }
 
 /// Current motion state
@@ -27,24 +156,113 @@ enum MotionState {
     },
 }
 
+/// Outcome of stepping across the grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Cell((u32, u32)),
+    Handoff(Direction),
+}
+
+/// Bounded jumplist of committed grid positions
+#[derive(Debug, Clone)]
+struct History {
+    entries: VecDeque<(u32, u32)>,
+    index: usize,
+}
+
+impl History {
+    fn new(start: (u32, u32)) -> Self {
+        Self {
+            entries: VecDeque::from([start]),
+            index: 0,
+        }
+    }
+
+    /// Append a committed position, dropping any forward entries and the oldest overflow
+    fn record(&mut self, pos: (u32, u32), capacity: usize) {
+        self.entries.truncate(self.index + 1);
+        self.entries.push_back(pos);
+        while self.entries.len() > capacity.max(1) {
+            self.entries.pop_front();
+        }
+        self.index = self.entries.len() - 1;
+    }
+
+    fn back(&mut self) -> Option<(u32, u32)> {
+        self.index = self.index.checked_sub(1)?;
+        self.entries.get(self.index).copied()
+    }
+
+    fn forward(&mut self) -> Option<(u32, u32)> {
+        let pos = self.entries.get(self.index + 1).copied()?;
+        self.index += 1;
+        Some(pos)
+    }
+}
+
 /// Motion controller with easing support
 pub struct MotionController {
     config: Arc<RwLock<Config>>,
     state: MotionState,
     current_grid_pos: (u32, u32),
     current_screen_pos: (f64, f64),
+    output_size: (u32, u32),
+    region: Region,
+    pending_handoff: Option<Direction>,
+    history: History,
+    clock: Box<dyn Clock>,
 }
 
 impl MotionController {
     pub fn new(config: Arc<RwLock<Config>>) -> Self {
+        Self::with_clock(config, SystemClock)
+    }
+
+    /// Time tweens with `clock` instead of the system clock
+    pub fn with_clock(config: Arc<RwLock<Config>>, clock: impl Clock + 'static) -> Self {
+        // Start on the center cell, and place the cursor there on the first update
+        let (grid_pos, screen_pos) = match config.try_read() {
+            Ok(config) => {
+                let center = resolve_target(JumpTarget::Center, (0, 0), &config);
+                (center, grid_to_screen(center, &config, Region::FULL))
+            }
+            Err(_) => ((0, 0), (0.5, 0.5)),
+        };
+
         Self {
             config,
-            state: MotionState::Idle,
-            current_grid_pos: (0, 0),
-            current_screen_pos: (0.5, 0.5), // Start at screen center
+            state: MotionState::Moving {
+                from: screen_pos,
+                to: screen_pos,
+                start_time: clock.now(),
+                duration: Duration::ZERO,
+            },
+            current_grid_pos: grid_pos,
+            current_screen_pos: screen_pos,
+            output_size: (1920, 1080),
+            region: Region::FULL,
+            pending_handoff: None,
+            history: History::new(grid_pos),
+            clock: Box::new(clock),
         }
     }
 
+    /// Set the pixel size of the target output, used for speed-based durations
+    pub fn set_output_size(&mut self, width: u32, height: u32) {
+        self.output_size = (width, height);
+    }
+
+    /// Spread the grid over `region` of the output, from the next move or
+    /// [`reposition`](Self::reposition) on
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+    }
+
+    /// Part of the output the grid is spread over
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
     /// Handle motion events
     pub fn handle_event(&mut self, event: MotionEvent) {
         let config = match self.config.try_read() {
@@ -52,65 +270,172 @@ impl MotionController {
             Err(_) => return,
         };
 
-        let (new_grid_pos, duration) = match event {
+        let step = match event {
             MotionEvent::Move { direction } => {
-                let new_pos = self.apply_direction(self.current_grid_pos, direction, 1, &config);
-                (new_pos, Duration::from_millis(config.movement.tween_ms))
+                self.apply_direction(self.current_grid_pos, direction, 1, &config)
+            }
+            MotionEvent::Dash { direction } => self.apply_direction(
+                self.current_grid_pos,
+                direction,
+                config.movement.dash_cells,
+                &config,
+            ),
+            MotionEvent::MoveBy { direction, cells } => {
+                self.apply_direction(self.current_grid_pos, direction, cells, &config)
+            }
+            MotionEvent::JumpTo { cell } => Step::Cell(clamp_cell(cell, &config)),
+            MotionEvent::Jump { target } => {
+                Step::Cell(resolve_target(target, self.current_grid_pos, &config))
             }
-            MotionEvent::Dash { direction } => {
-                let new_pos = self.apply_direction(
-                    self.current_grid_pos,
-                    direction,
-                    config.movement.dash_cells,
-                    &config,
-                );
-                (new_pos, Duration::from_millis(config.movement.tween_ms))
+            MotionEvent::Back | MotionEvent::Forward => {
+                let cell = match event {
+                    MotionEvent::Back => self.history.back(),
+                    _ => self.history.forward(),
+                };
+                let Some(cell) = cell else {
+                    debug!("No {:?} history entry", event);
+                    return;
+                };
+                Step::Cell(clamp_cell(cell, &config))
+            }
+        };
+        let record = !matches!(event, MotionEvent::Back | MotionEvent::Forward);
+
+        let new_grid_pos = match step {
+            Step::Cell(pos) => pos,
+            Step::Handoff(direction) => {
+                debug!("Handing off {:?} to the neighbouring output", direction);
+                self.pending_handoff = Some(direction);
+                return;
             }
         };
 
         if new_grid_pos != self.current_grid_pos {
             let from = self.current_screen_pos;
-            let to = self.grid_to_screen(new_grid_pos, &config);
+            let to = grid_to_screen(new_grid_pos, &config, self.region);
+
+            let cells = cell_distance(self.current_grid_pos, new_grid_pos);
+            let pixels = (((to.0 - from.0) * self.output_size.0 as f64).powi(2)
+                + ((to.1 - from.1) * self.output_size.1 as f64).powi(2))
+            .sqrt();
+            let duration = tween_duration(&config.movement, cells, pixels);
 
             self.state = MotionState::Moving {
                 from,
                 to,
-                start_time: Instant::now(),
+                start_time: self.clock.now(),
                 duration,
             };
             self.current_grid_pos = new_grid_pos;
+            if record {
+                self.history
+                    .record(new_grid_pos, config.movement.history_size);
+            }
+
+            debug!("Moving from {:?} to {:?} over {:?}", from, to, duration);
+        }
+    }
+
+    /// Grid cell the cursor is at or moving towards
+    pub fn grid_position(&self) -> (u32, u32) {
+        self.current_grid_pos
+    }
+
+    /// Normalized screen position of the cursor, mid-tween if moving
+    pub fn screen_position(&self) -> (f64, f64) {
+        self.current_screen_pos
+    }
+
+    /// Whether no tween is in progress
+    pub fn is_idle(&self) -> bool {
+        self.state == MotionState::Idle
+    }
+
+    /// Take the direction of a pending hand-off to a neighbouring output, if any
+    pub fn take_handoff(&mut self) -> Option<Direction> {
+        self.pending_handoff.take()
+    }
+
+    /// Place the cursor on the edge cell facing the output it came from
+    pub fn enter_output(&mut self, direction: Direction) {
+        let config = match self.config.try_read() {
+            Ok(config) => config,
+            Err(_) => return,
+        };
+
+        let (x, y) = self.current_grid_pos;
+        let (cols, rows) = config.grid.size();
+        let last_col = cols.saturating_sub(1);
+        let last_row = rows.saturating_sub(1);
+        let pos = match direction {
+            Direction::Up => (x, last_row),
+            Direction::Down => (x, 0),
+            Direction::Left => (last_col, y),
+            Direction::Right => (0, y),
+        };
+
+        let to = grid_to_screen(pos, &config, self.region);
+        self.current_grid_pos = pos;
+        self.current_screen_pos = to;
+        self.state = MotionState::Moving {
+            from: to,
+            to,
+            start_time: self.clock.now(),
+            duration: Duration::ZERO,
+        };
+    }
 
-            debug!("Moving from {:?} to {:?}", from, to);
+    /// Re-place the cursor after the grid changed, clamping it into a smaller grid
+    pub fn reposition(&mut self) {
+        let config = match self.config.try_read() {
+            Ok(config) => config,
+            Err(_) => return,
+        };
+
+        let pos = clamp_cell(self.current_grid_pos, &config);
+        let to = grid_to_screen(pos, &config, self.region);
+        if pos != self.current_grid_pos {
+            debug!(
+                "Clamped {:?} to {:?} in the new grid",
+                self.current_grid_pos, pos
+            );
         }
+
+        self.current_grid_pos = pos;
+        self.state = MotionState::Moving {
+            from: to,
+            to,
+            start_time: self.clock.now(),
+            duration: Duration::ZERO,
+        };
     }
 
     /// Update motion state and return current screen position if changed
     pub fn update(&mut self) -> Option<(f64, f64)> {
-        match &self.state {
-            MotionState::Idle => None,
-            MotionState::Moving {
-                from,
-                to,
-                start_time,
-                duration,
-            } => {
-                let elapsed = start_time.elapsed();
-
-                if elapsed >= *duration {
-                    self.current_screen_pos = *to;
-                    self.state = MotionState::Idle;
-                    Some(*to)
-                } else {
-                    let progress = elapsed.as_secs_f64() / duration.as_secs_f64();
-                    let eased_progress = ease_out_cubic(progress);
-
-                    let x = from.0 + (to.0 - from.0) * eased_progress;
-                    let y = from.1 + (to.1 - from.1) * eased_progress;
-
-                    self.current_screen_pos = (x, y);
-                    Some((x, y))
-                }
-            }
+        let MotionState::Moving {
+            from,
+            to,
+            start_time,
+            duration,
+        } = self.state
+        else {
+            return None;
+        };
+        let elapsed = self.clock.now().saturating_duration_since(start_time);
+
+        if elapsed >= duration {
+            self.current_screen_pos = to;
+            self.state = MotionState::Idle;
+            Some(to)
+        } else {
+            let progress = elapsed.as_secs_f64() / duration.as_secs_f64();
+            let eased_progress = ease_out_cubic(progress);
+
+            let x = from.0 + (to.0 - from.0) * eased_progress;
+            let y = from.1 + (to.1 - from.1) * eased_progress;
+
+            self.current_screen_pos = (x, y);
+            Some((x, y))
         }
     }
 
@@ -120,23 +445,130 @@ impl MotionController {
         direction: Direction,
         distance: u32,
         config: &Config,
-    ) -> (u32, u32) {
+    ) -> Step {
+        let grid = &config.grid;
+        let (cols, rows) = grid.size();
         let (x, y) = pos;
-        match direction {
-            Direction::Up => (x, y.saturating_sub(distance)),
-            Direction::Down => (x, (y + distance).min(config.grid.rows - 1)),
-            Direction::Left => (x.saturating_sub(distance), y),
-            Direction::Right => ((x + distance).min(config.grid.cols - 1), y),
+        let distance = distance as i64;
+
+        let step = match direction {
+            Direction::Up => step_axis(y, -distance, rows, grid.edge_y, grid.overshoot),
+            Direction::Down => step_axis(y, distance, rows, grid.edge_y, grid.overshoot),
+            Direction::Left => step_axis(x, -distance, cols, grid.edge_x, grid.overshoot),
+            Direction::Right => step_axis(x, distance, cols, grid.edge_x, grid.overshoot),
+        };
+
+        match (step, direction) {
+            (Some(y), Direction::Up | Direction::Down) => Step::Cell((x, y)),
+            (Some(x), Direction::Left | Direction::Right) => Step::Cell((x, y)),
+            (None, direction) => Step::Handoff(direction),
         }
     }
+}
+
+/// Map a grid cell to normalized screen coordinates within `region`
+///
+/// The layout is placed inside the grid's margins, which are fractions of the region.
+fn grid_to_screen(grid_pos: (u32, u32), config: &Config, region: Region) -> (f64, f64) {
+    let grid = &config.grid;
+    let (cols, rows) = grid.size();
+    let x = axis_position(grid_pos.0, cols, &grid.col_bounds, grid.anchor);
+    let y = axis_position(grid_pos.1, rows, &grid.row_bounds, grid.anchor);
+
+    let x = grid.margin_left + x * (1.0 - grid.margin_left - grid.margin_right);
+    let y = grid.margin_top + y * (1.0 - grid.margin_top - grid.margin_bottom);
+    (region.x + x * region.width, region.y + y * region.height)
+}
+
+/// Fraction along an axis of `count` positions for position `i`
+fn axis_position(i: u32, count: u32, bounds: &[f64], anchor: CellAnchor) -> f64 {
+    let i = i.min(count.saturating_sub(1)) as usize;
+    let bound = |i: usize| bounds.get(i).copied().unwrap_or(1.0);
+    match (bounds.is_empty(), anchor) {
+        (true, CellAnchor::Corner) => i as f64 / count.saturating_sub(1).max(1) as f64,
+        (true, CellAnchor::Center) => (i as f64 + 0.5) / count.max(1) as f64,
+        (false, CellAnchor::Corner) => bound(i),
+        (false, CellAnchor::Center) => (bound(i) + bound(i + 1)) / 2.0,
+    }
+}
+
+/// Clamp `cell` into the grid
+fn clamp_cell(cell: (u32, u32), config: &Config) -> (u32, u32) {
+    let (cols, rows) = config.grid.size();
+    (
+        cell.0.min(cols.saturating_sub(1)),
+        cell.1.min(rows.saturating_sub(1)),
+    )
+}
+
+/// Resolve an absolute jump target relative to the current cell
+fn resolve_target(target: JumpTarget, pos: (u32, u32), config: &Config) -> (u32, u32) {
+    let (cols, rows) = config.grid.size();
+    let last_col = cols.saturating_sub(1);
+    let last_row = rows.saturating_sub(1);
+    let (x, y) = pos;
+
+    match target {
+        JumpTarget::Center => (cols / 2, rows / 2),
+        JumpTarget::TopLeft => (0, 0),
+        JumpTarget::TopRight => (last_col, 0),
+        JumpTarget::BottomLeft => (0, last_row),
+        JumpTarget::BottomRight => (last_col, last_row),
+        JumpTarget::RowStart => (0, y),
+        JumpTarget::RowEnd => (last_col, y),
+        JumpTarget::ColumnStart => (x, 0),
+        JumpTarget::ColumnEnd => (x, last_row),
+        JumpTarget::Column(col) => (col.min(last_col), y),
+        JumpTarget::Row(row) => (x, row.min(last_row)),
+    }
+}
+
+/// Move `pos` by `delta` along an axis of `len` cells, returning `None` to hand off
+fn step_axis(pos: u32, delta: i64, len: u32, edge: EdgeMode, overshoot: Overshoot) -> Option<u32> {
+    let last = len.saturating_sub(1) as i64;
+    let target = pos as i64 + delta;
+    if (0..=last).contains(&target) {
+        return Some(target as u32);
+    }
 
-    fn grid_to_screen(&self, grid_pos: (u32, u32), config: &Config) -> (f64, f64) {
-        let x = grid_pos.0 as f64 / (config.grid.cols - 1) as f64;
-        let y = grid_pos.1 as f64 / (config.grid.rows - 1) as f64;
-        (x, y)
+    let edge_cell = target.clamp(0, last) as u32;
+    match edge {
+        EdgeMode::Wrap => Some(target.rem_euclid(last + 1) as u32),
+        EdgeMode::NextOutput if pos == edge_cell => None,
+        EdgeMode::Clamp | EdgeMode::NextOutput => match overshoot {
+            Overshoot::Stop => Some(edge_cell),
+            Overshoot::Refuse => Some(pos),
+        },
     }
 }
 
+/// Compute the tween duration for a move of `cells` grid cells covering `pixels` on screen
+pub fn tween_duration(movement: &MovementConfig, cells: f64, pixels: f64) -> Duration {
+    let ms = match movement.duration_mode {
+        DurationMode::Fixed => return Duration::from_millis(movement.tween_ms),
+        DurationMode::PerCell => cells * movement.ms_per_cell as f64,
+        DurationMode::Speed if movement.speed_px_per_sec > 0.0 => {
+            pixels / movement.speed_px_per_sec * 1000.0
+        }
+        DurationMode::Speed => movement.tween_ms as f64,
+    };
+
+    // `Duration::from_secs_f64` panics on NaN or infinity, which clamping lets through
+    if !ms.is_finite() {
+        return Duration::from_millis(movement.tween_ms);
+    }
+    let min = movement.min_tween_ms.min(movement.max_tween_ms) as f64;
+    let max = movement.max_tween_ms as f64;
+    Duration::from_secs_f64(ms.clamp(min, max) / 1000.0)
+}
+
+/// Euclidean distance between two grid cells, in cells
+fn cell_distance(from: (u32, u32), to: (u32, u32)) -> f64 {
+    let dx = to.0 as f64 - from.0 as f64;
+    let dy = to.1 as f64 - from.1 as f64;
+    (dx * dx + dy * dy).sqrt()
+}
+
 /// Cubic ease-out function for smooth movement
 fn ease_out_cubic(t: f64) -> f64 {
     let t = t.clamp(0.0, 1.0);
@@ -160,32 +592,43 @@ mod tests {
         use crate::config::{Config, DisplayConfig, GridConfig, InputConfig, MovementConfig};
 
         let config = Config {
-            grid: GridConfig { cols: 10, rows: 10 },
+            grid: GridConfig {
+                cols: 10,
+                rows: 10,
+                ..Default::default()
+            },
             movement: MovementConfig {
                 dash_cells: 3,
                 tween_ms: 100,
+                ..Default::default()
             },
             input: InputConfig {
                 keyboard_device: None,
                 gamepad_device: None,
+                ..Default::default()
             },
             display: DisplayConfig {
                 target_monitor: "auto".to_string(),
+                ..Default::default()
             },
+            ..Default::default()
         };
 
         let mut controller = MotionController::new(Arc::new(RwLock::new(config)));
 
+        // Starts on the center cell
+        assert_eq!(controller.current_grid_pos, (5, 5));
+
         // Test normal movement
         controller.handle_event(MotionEvent::Move {
             direction: Direction::Right,
         });
-        assert_eq!(controller.current_grid_pos, (1, 0));
+        assert_eq!(controller.current_grid_pos, (6, 5));
 
         // Test dash movement
         controller.handle_event(MotionEvent::Dash {
             direction: Direction::Right,
         });
-        assert_eq!(controller.current_grid_pos, (4, 0));
+        assert_eq!(controller.current_grid_pos, (9, 5));
     }
 }
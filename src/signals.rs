@@ -0,0 +1,68 @@
+//! Unix signal handling
+//!
+//! Signals are turned into control requests, so they behave exactly like the
+//! matching `gridpointer` client commands.
+
+use crate::ipc::{self, Command, Request};
+use tokio::signal::unix::{Signal, SignalKind, signal};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+
+/// Handlers for the signals the daemon responds to
+///
+/// | Signal          | Request  |
+/// |-----------------|----------|
+/// | SIGTERM, SIGINT | `quit`   |
+/// | SIGHUP          | `reload` |
+/// | SIGUSR1         | `pause`  |
+/// | SIGUSR2         | `resume` |
+pub struct Signals {
+    terminate: Signal,
+    interrupt: Signal,
+    hangup: Signal,
+    user1: Signal,
+    user2: Signal,
+}
+
+impl Signals {
+    /// Install the handlers, replacing the default actions
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            terminate: signal(SignalKind::terminate())?,
+            interrupt: signal(SignalKind::interrupt())?,
+            hangup: signal(SignalKind::hangup())?,
+            user1: signal(SignalKind::user_defined1())?,
+            user2: signal(SignalKind::user_defined2())?,
+        })
+    }
+
+    /// Forward signals to `tx` as requests until shutdown
+    pub async fn run(
+        mut self,
+        tx: mpsc::UnboundedSender<Command>,
+        mut shutdown: broadcast::Receiver<()>,
+    ) -> anyhow::Result<()> {
+        loop {
+            let (name, request) = tokio::select! {
+                _ = self.terminate.recv() => ("SIGTERM", Request::Quit),
+                _ = self.interrupt.recv() => ("SIGINT", Request::Quit),
+                _ = self.hangup.recv() => ("SIGHUP", Request::Reload),
+                _ = self.user1.recv() => ("SIGUSR1", Request::Pause),
+                _ = self.user2.recv() => ("SIGUSR2", Request::Resume),
+                _ = shutdown.recv() => break,
+            };
+
+            info!("Received {}", name);
+            let response = ipc::dispatch(&tx, request).await;
+            if !response.ok {
+                warn!(
+                    "Handling {} failed: {}",
+                    name,
+                    response.error.unwrap_or_default()
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
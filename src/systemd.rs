@@ -0,0 +1,116 @@
+//! systemd service integration without linking libsystemd
+//!
+//! Speaks the `sd_notify(3)` datagram protocol for readiness, status and
+//! watchdog messages, and picks up sockets passed in by socket activation
+//! (`sd_listen_fds(3)`). Everything is a no-op outside a systemd service.
+
+use std::ffi::OsStr;
+use std::io;
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tracing::debug;
+
+/// First file descriptor passed by socket activation
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Send a raw `sd_notify` message such as `READY=1` to `$NOTIFY_SOCKET`
+///
+/// Returns `Ok(false)` when not running under systemd.
+pub fn notify(state: &str) -> io::Result<bool> {
+    notify_to(std::env::var_os("NOTIFY_SOCKET").as_deref(), state)
+}
+
+/// Send a raw `sd_notify` message to `socket`, as [`notify`] does with `$NOTIFY_SOCKET`
+pub fn notify_to(socket: Option<&OsStr>, state: &str) -> io::Result<bool> {
+    let Some(socket) = socket else {
+        return Ok(false);
+    };
+    let socket = socket.to_string_lossy();
+
+    // A leading '@' names a socket in the abstract namespace
+    let addr = match socket.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(&*socket)?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(true)
+}
+
+/// Tell systemd start-up has finished
+pub fn ready(status: &str) {
+    send(&format!("READY=1\nSTATUS={}", status));
+}
+
+/// Update the status line shown by `systemctl status`
+pub fn status(status: &str) {
+    send(&format!("STATUS={}", status));
+}
+
+/// Tell systemd the daemon is shutting down
+pub fn stopping() {
+    send("STOPPING=1");
+}
+
+/// Keep the service watchdog from restarting the daemon
+pub fn watchdog() {
+    send("WATCHDOG=1");
+}
+
+fn send(state: &str) {
+    if let Err(e) = notify(state) {
+        debug!("sd_notify failed: {}", e);
+    }
+}
+
+/// How often to send [`watchdog`] pings, if the service has a watchdog
+///
+/// Half of `WatchdogSec=`, so a late tick doesn't trip it.
+pub fn watchdog_interval() -> Option<Duration> {
+    watchdog_interval_from(
+        std::env::var_os("WATCHDOG_USEC").as_deref(),
+        std::env::var_os("WATCHDOG_PID").as_deref(),
+    )
+}
+
+/// [`watchdog_interval`] for the given `$WATCHDOG_USEC` and `$WATCHDOG_PID`
+pub fn watchdog_interval_from(usec: Option<&OsStr>, pid: Option<&OsStr>) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.to_str()?.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+
+    let usec: u64 = usec?.to_str()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+/// Take the sockets passed in by socket activation
+///
+/// Only the first call returns them; the descriptors are owned from then on.
+pub fn listen_fds() -> Vec<OwnedFd> {
+    static TAKEN: AtomicBool = AtomicBool::new(false);
+
+    let pid = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok());
+    if pid != Some(std::process::id()) || TAKEN.swap(true, Ordering::SeqCst) {
+        return Vec::new();
+    }
+    let count: RawFd = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(0);
+
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .filter_map(|fd| {
+            // SAFETY: systemd hands these descriptors to this process, and the
+            // guard above makes sure they are only wrapped once
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            // Duplicating sets close-on-exec, which systemd leaves unset
+            fd.try_clone().ok()
+        })
+        .collect()
+}
@@ -1,10 +1,14 @@
 //! Wayland integration for virtual pointer control
 
-use crate::error::{GridPointerError, Result};
+use crate::error::Result;
+use crate::focus::Geometry;
+use crate::input::Direction;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 use tracing::{debug, info, warn};
 use wayland_client::{
-    protocol::{wl_compositor, wl_output, wl_registry, wl_seat},
+    protocol::{wl_compositor, wl_output, wl_pointer, wl_registry, wl_seat},
     Connection, Dispatch, QueueHandle, EventQueue,
 };
 use wayland_protocols_wlr::virtual_pointer::v1::client::{
@@ -13,9 +17,11 @@ use wayland_protocols_wlr::virtual_pointer::v1::client::{
 
 /// Wayland manager for virtual pointer control
 pub struct WaylandManager {
-    connection: Connection,
+    /// Kept open for the lifetime of the manager
+    _connection: Connection,
     virtual_pointer: Option<zwlr_virtual_pointer_v1::ZwlrVirtualPointerV1>,
     outputs: Vec<OutputInfo>,
+    active_output: usize,
     queue: Arc<Mutex<EventQueue<AppState>>>,
 }
 
@@ -23,11 +29,52 @@ pub struct WaylandManager {
 struct OutputInfo {
     output: wl_output::WlOutput,
     name: String,
+    /// Whether `name` came from the compositor rather than the registry id,
+    /// which changes across restarts
+    named: bool,
+    x: i32,
+    y: i32,
     width: i32,
     height: i32,
+    /// Refresh rate of the current mode in mHz, 0 if unknown
+    refresh: i32,
     scale: i32,
 }
 
+impl OutputInfo {
+    /// Position and logical size; the mode is in physical pixels
+    fn geometry(&self) -> Geometry {
+        let scale = self.scale.max(1);
+        Geometry {
+            x: self.x,
+            y: self.y,
+            width: self.width / scale,
+            height: self.height / scale,
+        }
+    }
+}
+
+/// Mouse buttons the virtual pointer can press
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Button {
+    #[default]
+    Left,
+    Right,
+    Middle,
+}
+
+impl Button {
+    /// Linux input event code for the button
+    fn code(self) -> u32 {
+        match self {
+            Button::Left => 0x110,   // BTN_LEFT
+            Button::Right => 0x111,  // BTN_RIGHT
+            Button::Middle => 0x112, // BTN_MIDDLE
+        }
+    }
+}
+
 struct AppState {
     outputs: Vec<OutputInfo>,
     virtual_pointer_manager: Option<zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1>,
@@ -50,10 +97,12 @@ impl WaylandManager {
             seat: None,
         };
         
-        let registry = display.get_registry(&qh, ());
+        let _registry = display.get_registry(&qh, ());
         
         // Initial roundtrip to get globals
-        event_queue.blocking_dispatch(&mut state)?;
+        event_queue.roundtrip(&mut state)?;
+        // Second roundtrip for the names, modes and positions of the bound outputs
+        event_queue.roundtrip(&mut state)?;
         
         let virtual_pointer = if let (Some(manager), Some(seat)) = 
             (&state.virtual_pointer_manager, &state.seat) {
@@ -64,9 +113,10 @@ impl WaylandManager {
         };
         
         Ok(Self {
-            connection,
+            _connection: connection,
             virtual_pointer,
             outputs: state.outputs,
+            active_output: 0,
             queue: Arc::new(Mutex::new(event_queue)),
         })
     }
@@ -74,24 +124,24 @@ impl WaylandManager {
     /// Move cursor to normalized screen coordinates (0.0-1.0)
     pub async fn move_cursor(&self, x: f64, y: f64) -> Result<()> {
         if let Some(pointer) = &self.virtual_pointer {
-            // Get primary output dimensions
-            let (width, height) = self.get_primary_output_size();
+            // Get the active output's logical area and the extent of the whole layout
+            let (active, extent_w, extent_h) = self.layout_offset_and_extent();
             
-            // Convert normalized coordinates to absolute pixels
-            let abs_x = (x * width as f64) as u32;
-            let abs_y = (y * height as f64) as u32;
+            // Convert normalized coordinates to absolute logical pixels within the layout
+            let abs_x = active.x as u32 + (x * active.width as f64) as u32;
+            let abs_y = active.y as u32 + (y * active.height as f64) as u32;
             
             pointer.motion_absolute(
                 0, // time
                 abs_x,
                 abs_y,
-                width as u32,
-                height as u32,
+                extent_w,
+                extent_h,
             );
             pointer.frame();
             
             // Flush the connection
-            if let Ok(mut queue) = self.queue.lock() {
+            if let Ok(queue) = self.queue.lock() {
                 let _ = queue.flush();
             }
             
@@ -102,33 +152,219 @@ impl WaylandManager {
     
     /// Perform left mouse click
     pub async fn click_left(&self) -> Result<()> {
+        self.click(Button::Left).await
+    }
+
+    /// Click `button` at the current position
+    pub async fn click(&self, button: Button) -> Result<()> {
         if let Some(pointer) = &self.virtual_pointer {
-            // Press
-            pointer.button(0, 0x110, 1); // BTN_LEFT = 0x110
+            pointer.button(0, button.code(), wl_pointer::ButtonState::Pressed);
             pointer.frame();
-            
-            // Release
-            pointer.button(1, 0x110, 0);
+            pointer.button(1, button.code(), wl_pointer::ButtonState::Released);
             pointer.frame();
-            
-            // Flush
-            if let Ok(mut queue) = self.queue.lock() {
+
+            if let Ok(queue) = self.queue.lock() {
                 let _ = queue.flush();
             }
-            
-            debug!("Left click performed");
+
+            debug!("{:?} click performed", button);
         }
         Ok(())
     }
+
+    /// Scroll by whole wheel steps, positive values scroll right and down
+    pub async fn scroll(&self, x: i32, y: i32) -> Result<()> {
+        if let Some(pointer) = &self.virtual_pointer {
+            pointer.axis_source(wl_pointer::AxisSource::Wheel);
+            for (axis, steps) in [
+                (wl_pointer::Axis::HorizontalScroll, x),
+                (wl_pointer::Axis::VerticalScroll, y),
+            ] {
+                if steps != 0 {
+                    // 15 units per step matches a typical wheel detent
+                    pointer.axis_discrete(0, axis, steps as f64 * 15.0, steps);
+                }
+            }
+            pointer.frame();
+
+            if let Ok(queue) = self.queue.lock() {
+                let _ = queue.flush();
+            }
+
+            debug!("Scrolled by ({}, {})", x, y);
+        }
+        Ok(())
+    }
+
+    /// Press and hold the left mouse button
+    pub async fn press_left(&self) -> Result<()> {
+        self.left_button(wl_pointer::ButtonState::Pressed);
+        debug!("Left button pressed");
+        Ok(())
+    }
+
+    /// Release the left mouse button
+    pub async fn release_left(&self) -> Result<()> {
+        self.left_button(wl_pointer::ButtonState::Released);
+        debug!("Left button released");
+        Ok(())
+    }
+
+    fn left_button(&self, state: wl_pointer::ButtonState) {
+        if let Some(pointer) = &self.virtual_pointer {
+            pointer.button(0, Button::Left.code(), state);
+            pointer.frame();
+
+            if let Ok(queue) = self.queue.lock() {
+                let _ = queue.flush();
+            }
+        }
+    }
     
-    fn get_primary_output_size(&self) -> (i32, i32) {
-        if let Some(output) = self.outputs.first() {
+    /// Make the neighbouring output in `direction` the active one
+    ///
+    /// Returns `false` if there is no output on that side.
+    pub fn switch_output(&mut self, direction: Direction) -> bool {
+        let Some(current) = self.outputs.get(self.active_output).map(OutputInfo::geometry) else {
+            return false;
+        };
+
+        // Outputs touching the active one on that side and overlapping it on the other axis,
+        // compared in logical pixels since positions are logical and modes physical
+        let neighbour = self
+            .outputs
+            .iter()
+            .map(OutputInfo::geometry)
+            .enumerate()
+            .filter(|(_, o)| match direction {
+                Direction::Left => o.x + o.width == current.x,
+                Direction::Right => o.x == current.x + current.width,
+                Direction::Up => o.y + o.height == current.y,
+                Direction::Down => o.y == current.y + current.height,
+            })
+            .filter(|(_, o)| match direction {
+                Direction::Left | Direction::Right => {
+                    o.y < current.y + current.height && current.y < o.y + o.height
+                }
+                Direction::Up | Direction::Down => {
+                    o.x < current.x + current.width && current.x < o.x + o.width
+                }
+            })
+            .map(|(index, _)| index)
+            .next();
+
+        match neighbour {
+            Some(index) => {
+                info!("Switched to output {}", self.outputs[index].name);
+                self.active_output = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Name of the output the cursor is mapped onto
+    pub fn active_output_name(&self) -> Option<String> {
+        self.outputs.get(self.active_output).map(|o| o.name.clone())
+    }
+
+    /// Connector name of the active output, if the compositor reported one
+    ///
+    /// Unlike [`active_output_name`](Self::active_output_name) this is stable
+    /// across compositor restarts, so it is safe to persist.
+    pub fn active_output_connector(&self) -> Option<String> {
+        self.outputs
+            .get(self.active_output)
+            .filter(|o| o.named)
+            .map(|o| o.name.clone())
+    }
+
+    /// Make the output called `name` the active one
+    ///
+    /// Returns `false` if no such output exists.
+    pub fn focus_output(&mut self, name: &str) -> bool {
+        match self.outputs.iter().position(|o| o.name == name) {
+            Some(index) => {
+                if index != self.active_output {
+                    info!("Switched to output {}", name);
+                    self.active_output = index;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Refresh rate of the output the cursor is mapped onto in Hz, if known
+    pub fn refresh_rate(&self) -> Option<f64> {
+        self.outputs
+            .get(self.active_output)
+            .filter(|o| o.refresh > 0)
+            .map(|o| o.refresh as f64 / 1000.0)
+    }
+
+    /// Map the cursor onto the output named by `target_monitor`
+    ///
+    /// `auto` selects the primary output. Returns `false` if no such output exists.
+    pub fn focus_target(&mut self, target: &str) -> bool {
+        if target == "auto" {
+            self.active_output = 0;
+            return true;
+        }
+        self.focus_output(target)
+    }
+
+    /// Pixel dimensions of the output the cursor is mapped onto
+    pub fn get_primary_output_size(&self) -> (i32, i32) {
+        if let Some(output) = self.outputs.get(self.active_output) {
             (output.width, output.height)
         } else {
             // Fallback dimensions
             (1920, 1080)
         }
     }
+
+    /// Area of the active output in logical pixels of the output layout
+    pub fn output_geometry(&self) -> Option<Geometry> {
+        self.outputs.get(self.active_output).map(OutputInfo::geometry)
+    }
+
+    /// Make the output containing the layout point `(x, y)` the active one
+    ///
+    /// Returns `false` if no output contains it.
+    pub fn focus_point(&mut self, x: i32, y: i32) -> bool {
+        match self.outputs.iter().position(|o| o.geometry().contains(x, y)) {
+            Some(index) => {
+                if index != self.active_output {
+                    info!("Switched to output {}", self.outputs[index].name);
+                    self.active_output = index;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Logical area of the active output relative to the output layout, and the layout's extent
+    fn layout_offset_and_extent(&self) -> (Geometry, u32, u32) {
+        let Some(active) = self.output_geometry() else {
+            let (width, height) = self.get_primary_output_size();
+            let active = Geometry { x: 0, y: 0, width, height };
+            return (active, width as u32, height as u32);
+        };
+
+        let outputs: Vec<Geometry> = self.outputs.iter().map(OutputInfo::geometry).collect();
+        let min_x = outputs.iter().map(|o| o.x).min().unwrap_or(0);
+        let min_y = outputs.iter().map(|o| o.y).min().unwrap_or(0);
+        let max_x = outputs.iter().map(|o| o.x + o.width).max().unwrap_or(0);
+        let max_y = outputs.iter().map(|o| o.y + o.height).max().unwrap_or(0);
+
+        (
+            Geometry { x: active.x - min_x, y: active.y - min_y, ..active },
+            (max_x - min_x) as u32,
+            (max_y - min_y) as u32,
+        )
+    }
 }
 
 // Wayland protocol implementations
@@ -154,12 +390,17 @@ impl Dispatch<wl_registry::WlRegistry, ()> for AppState {
                     state.seat = Some(seat);
                 }
                 "wl_output" => {
-                    let output = registry.bind::<wl_output::WlOutput, _, _>(name, version.min(2), qh, ());
+                    // Version 4 adds the connector name, e.g. DP-1
+                    let output = registry.bind::<wl_output::WlOutput, _, _>(name, version.min(4), qh, ());
                     state.outputs.push(OutputInfo {
                         output,
                         name: format!("output-{}", name),
+                        named: false,
+                        x: 0,
+                        y: 0,
                         width: 1920,
                         height: 1080,
+                        refresh: 0,
                         scale: 1,
                     });
                 }
@@ -219,15 +460,27 @@ impl Dispatch<wl_output::WlOutput, ()> for AppState {
     ) {
         if let Some(output_info) = state.outputs.iter_mut().find(|o| &o.output == output) {
             match event {
-                wl_output::Event::Mode { width, height, .. } => {
-                    output_info.width = width;
-                    output_info.height = height;
+                wl_output::Event::Geometry { x, y, .. } => {
+                    output_info.x = x;
+                    output_info.y = y;
+                }
+                // Outputs may list every mode they support; only the current one applies
+                wl_output::Event::Mode { flags, width, height, refresh } => {
+                    let current = flags
+                        .into_result()
+                        .is_ok_and(|flags| flags.contains(wl_output::Mode::Current));
+                    if current {
+                        output_info.width = width;
+                        output_info.height = height;
+                        output_info.refresh = refresh;
+                    }
                 }
                 wl_output::Event::Scale { factor } => {
                     output_info.scale = factor;
                 }
                 wl_output::Event::Name { name } => {
                     output_info.name = name;
+                    output_info.named = true;
                 }
                 _ => {}
             }
@@ -0,0 +1,105 @@
+//! Tests for command-line parsing
+
+use clap::Parser;
+use gridpointer::cli::{Cli, Command};
+use gridpointer::input::Direction;
+use gridpointer::ipc::Request;
+use gridpointer::wl::Button;
+use std::path::PathBuf;
+
+fn parse(args: &[&str]) -> Cli {
+    Cli::try_parse_from(std::iter::once("gridpointer").chain(args.iter().copied())).unwrap()
+}
+
+#[test]
+fn test_daemon_arguments() {
+    // Without a subcommand the daemon runs in the foreground
+    let cli = parse(&[]);
+    assert!(cli.command.is_none());
+
+    let cli = parse(&[
+        "daemon",
+        "--config",
+        "/tmp/grid.toml",
+        "--log-level",
+        "debug",
+        "--set",
+        "grid.cols=30",
+        "--set",
+        "display.target_monitor=DP-1",
+    ]);
+    match cli.command {
+        Some(Command::Daemon(args)) => {
+            assert_eq!(args.config, Some(PathBuf::from("/tmp/grid.toml")));
+            assert_eq!(args.log_level.as_deref(), Some("debug"));
+            assert!(!args.foreground);
+            let overrides: Vec<_> = args
+                .overrides
+                .iter()
+                .map(|setting| format!("{}={}", setting.key, setting.value))
+                .collect();
+            assert_eq!(
+                overrides,
+                ["grid.cols=30", "display.target_monitor=\"DP-1\""]
+            );
+        }
+        other => panic!("expected daemon command, got {:?}", other),
+    }
+
+    assert!(parse(&["-V"]).version);
+    assert!(Cli::try_parse_from(["gridpointer", "daemon", "--set", "grid.colls=30"]).is_err());
+}
+
+#[test]
+fn test_client_requests() {
+    let request = |args: &[&str]| parse(args).command.unwrap().request();
+
+    assert_eq!(
+        request(&["jump", "3", "4"]),
+        Some(Request::Jump { col: 3, row: 4 })
+    );
+    assert_eq!(
+        request(&["move", "left"]),
+        Some(Request::Move {
+            direction: Direction::Left,
+            cells: 1
+        })
+    );
+    assert_eq!(
+        request(&["move", "down", "5"]),
+        Some(Request::Move {
+            direction: Direction::Down,
+            cells: 5
+        })
+    );
+    assert_eq!(
+        request(&["click", "right"]),
+        Some(Request::Click {
+            button: Button::Right
+        })
+    );
+    assert_eq!(
+        request(&["scroll", "--y", "-3"]),
+        Some(Request::Scroll { x: 0, y: -3 })
+    );
+    assert_eq!(request(&["status", "--json"]), Some(Request::Status));
+    assert_eq!(request(&["toggle"]), Some(Request::Toggle));
+    assert_eq!(
+        request(&["config", "--file", "/tmp/grid.toml"]),
+        Some(Request::Config)
+    );
+    assert_eq!(
+        request(&["profile", "gaming"]),
+        Some(Request::Profile {
+            name: Some("gaming".to_string())
+        })
+    );
+    assert_eq!(request(&["profile"]), Some(Request::Profile { name: None }));
+    assert_eq!(request(&["daemon"]), None);
+
+    // The socket option is accepted after the subcommand too
+    let cli = parse(&["quit", "--socket", "/tmp/gp.sock"]);
+    assert_eq!(cli.socket, Some(PathBuf::from("/tmp/gp.sock")));
+
+    assert!(Cli::try_parse_from(["gridpointer", "move", "sideways"]).is_err());
+}
@@ -1,8 +1,14 @@
 //! Tests for configuration management
 
-use gridpointer::config::{Config, ConfigManager, GridConfig, MovementConfig};
+use gridpointer::config::{
+    Config, ConfigChange, ConfigManager, ConfigOverride, GridConfig, MovementConfig,
+};
+use gridpointer::error::GridPointerError;
 use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
 use tempfile::TempDir;
+use tokio::sync::broadcast;
 
 #[tokio::test]
 async fn test_default_config() {
@@ -23,3 +29,405 @@ async fn test_config_serialization() {
     assert_eq!(config.grid.rows, parsed.grid.rows);
     assert_eq!(config.movement.dash_cells, parsed.movement.dash_cells);
 }
+
+#[test]
+fn test_validation_reports_every_issue_with_location() {
+    let content = r#"[grid]
+cols = 1
+rows = 12
+
+[movement]
+dash_cells = 5
+tween_ms = 150
+min_tween_ms = 500
+max_tween_ms = 100
+
+[input]
+
+[display]
+target_monitor = "auto"
+
+[marks]
+t = { col = 30, row = 0 }
+"#;
+
+    let issues = match Config::from_toml(content) {
+        Err(GridPointerError::InvalidConfig(issues)) => issues,
+        other => panic!("expected InvalidConfig, got {:?}", other),
+    };
+    let found: Vec<_> = issues
+        .iter()
+        .map(|issue| (issue.key.as_str(), issue.location))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("grid.cols", Some((2, 8))),
+            ("movement.min_tween_ms", Some((8, 16))),
+            ("marks.t.col", Some((17, 13))),
+        ]
+    );
+    assert_eq!(
+        issues[0].to_string(),
+        "line 2, column 8: grid.cols: must be at least 2, got 1"
+    );
+}
+
+#[test]
+fn test_layout_validation() {
+    let content = r#"[grid]
+col_bounds = [0.0, 0.6, 0.4, 1.5]
+row_bounds = [0.0, 1.0]
+anchor = "center"
+margin_left = 0.5
+margin_right = 0.5
+
+[marks]
+t = { col = 2, row = 0 }
+"#;
+
+    let issues = match Config::from_toml(content) {
+        Err(GridPointerError::InvalidConfig(issues)) => issues,
+        other => panic!("expected InvalidConfig, got {:?}", other),
+    };
+    let found: Vec<_> = issues
+        .iter()
+        .map(|issue| (issue.key.as_str(), issue.location))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("grid.col_bounds.2", Some((2, 25))),
+            ("grid.col_bounds.3", Some((2, 30))),
+            ("grid.row_bounds", Some((3, 14))),
+            ("grid.margin_right", Some((6, 16))),
+        ]
+    );
+    assert_eq!(
+        issues[2].message,
+        "gives 1 positions with this anchor, at least 2 are needed"
+    );
+
+    // Marks are checked against the layout, not `cols`
+    let content = "[grid]\ncol_bounds = [0.0, 0.5, 1.0]\n\n[marks]\nt = { col = 3, row = 0 }\n";
+    let Err(GridPointerError::InvalidConfig(issues)) = Config::from_toml(content) else {
+        panic!("expected InvalidConfig");
+    };
+    assert_eq!(issues[0].message, "3 is outside the grid's 3 columns");
+
+    // A small leftover cols doesn't matter once bounds set the columns
+    let content =
+        "[grid]\ncols = 1\nrows = 0\ncol_bounds = [0.0, 0.5, 1.0]\nrow_bounds = [0.0, 1.0]\n";
+    let config = Config::from_toml(content).unwrap();
+    assert_eq!(config.grid.size(), (3, 2));
+}
+
+#[tokio::test]
+async fn test_missing_explicit_config() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("gridpointr").join("config.toml");
+
+    // Only the default location is created; a mistyped path is an error
+    let Err(error) = ConfigManager::with_path(path.clone()).await else {
+        panic!("a missing explicit config was accepted");
+    };
+    let error = format!("{:#}", error);
+    assert!(error.contains(&path.display().to_string()), "{}", error);
+    assert!(!path.parent().unwrap().exists());
+}
+
+#[tokio::test]
+async fn test_reload_keeps_previous_valid_config() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+    fs::write(&path, "").unwrap();
+    let manager = ConfigManager::with_path(path.clone()).await.unwrap();
+    let config = manager.get_config();
+
+    let valid = Config {
+        grid: GridConfig {
+            cols: 30,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut reloads = manager.subscribe_reloads();
+    fs::write(&path, toml::to_string(&valid).unwrap()).unwrap();
+    assert_eq!(manager.reload().await.unwrap(), vec![ConfigChange::Grid]);
+    assert_eq!(reloads.recv().await.unwrap(), vec![ConfigChange::Grid]);
+    assert_eq!(config.read().await.grid.cols, 30);
+
+    // A broken file is refused and the running config stays as it was
+    let mut broken = valid.clone();
+    broken.grid.rows = 0;
+    broken.movement = MovementConfig {
+        dash_cells: 0,
+        ..Default::default()
+    };
+    fs::write(&path, toml::to_string(&broken).unwrap()).unwrap();
+    let error = format!("{:#}", manager.reload().await.unwrap_err());
+    assert!(error.contains("grid.rows"), "{}", error);
+    assert!(error.contains("movement.dash_cells"), "{}", error);
+    assert_eq!(config.read().await.grid.cols, 30);
+    assert_eq!(config.read().await.grid.rows, 12);
+}
+
+/// The next reload, if one happens within a second
+async fn next_reload(
+    reloads: &mut broadcast::Receiver<Vec<ConfigChange>>,
+) -> Option<Vec<ConfigChange>> {
+    tokio::time::timeout(Duration::from_secs(1), reloads.recv())
+        .await
+        .ok()
+        .map(|changes| changes.unwrap())
+}
+
+#[tokio::test]
+async fn test_watch_symlinked_config() {
+    let dir = TempDir::new().unwrap();
+    let dotfiles = dir.path().join("dotfiles");
+    let config_dir = dir.path().join("config");
+    fs::create_dir(&dotfiles).unwrap();
+    fs::create_dir(&config_dir).unwrap();
+
+    let with_cols = |cols| {
+        toml::to_string(&Config {
+            grid: GridConfig {
+                cols,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap()
+    };
+    let target = dotfiles.join("gridpointer.toml");
+    fs::write(&target, with_cols(20)).unwrap();
+    let path = config_dir.join("config.toml");
+    std::os::unix::fs::symlink(&target, &path).unwrap();
+
+    let manager = Arc::new(ConfigManager::with_path(path.clone()).await.unwrap());
+    let config = manager.get_config();
+    let mut reloads = manager.subscribe_reloads();
+    let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+    let watcher = tokio::spawn({
+        let manager = manager.clone();
+        async move { manager.watch_config(shutdown_rx).await }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Other files in either directory are ignored
+    fs::write(config_dir.join("notes.txt"), "").unwrap();
+    fs::write(dotfiles.join("other.toml"), "").unwrap();
+    assert_eq!(next_reload(&mut reloads).await, None);
+
+    // An atomic save of the link target followed by another write reloads once
+    let temp = dotfiles.join(".gridpointer.toml.swp");
+    fs::write(&temp, with_cols(30)).unwrap();
+    fs::rename(&temp, &target).unwrap();
+    fs::write(&target, with_cols(31)).unwrap();
+    assert_eq!(
+        next_reload(&mut reloads).await,
+        Some(vec![ConfigChange::Grid])
+    );
+    assert_eq!(next_reload(&mut reloads).await, None);
+    assert_eq!(config.read().await.grid.cols, 31);
+
+    // Re-pointing the link by removing and recreating it
+    let other = dotfiles.join("laptop.toml");
+    fs::write(&other, with_cols(40)).unwrap();
+    fs::remove_file(&path).unwrap();
+    std::os::unix::fs::symlink(&other, &path).unwrap();
+    assert_eq!(
+        next_reload(&mut reloads).await,
+        Some(vec![ConfigChange::Grid])
+    );
+    assert_eq!(config.read().await.grid.cols, 40);
+
+    // The new target is followed from then on
+    fs::write(&other, with_cols(41)).unwrap();
+    assert!(next_reload(&mut reloads).await.is_some());
+    assert_eq!(config.read().await.grid.cols, 41);
+
+    shutdown_tx.send(()).unwrap();
+    watcher.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_watch_relative_config_path() {
+    let dir = TempDir::new().unwrap();
+    let with_cols = |cols| format!("[grid]\ncols = {}\n", cols);
+    fs::write(dir.path().join("config.toml"), with_cols(20)).unwrap();
+
+    // As given by `--config config.toml`; no other test here uses relative paths
+    std::env::set_current_dir(dir.path()).unwrap();
+    let manager = Arc::new(
+        ConfigManager::with_path("config.toml".into())
+            .await
+            .unwrap(),
+    );
+    let mut reloads = manager.subscribe_reloads();
+    let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+    let watcher = tokio::spawn({
+        let manager = manager.clone();
+        async move { manager.watch_config(shutdown_rx).await }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    fs::write(dir.path().join("config.toml"), with_cols(30)).unwrap();
+    assert_eq!(
+        next_reload(&mut reloads).await,
+        Some(vec![ConfigChange::Grid])
+    );
+    assert_eq!(manager.get_config().read().await.grid.cols, 30);
+
+    // Still followed after the file was briefly gone and could not be resolved
+    fs::remove_file(dir.path().join("config.toml")).unwrap();
+    assert_eq!(next_reload(&mut reloads).await, None);
+    fs::write(dir.path().join("config.toml"), with_cols(40)).unwrap();
+    assert_eq!(
+        next_reload(&mut reloads).await,
+        Some(vec![ConfigChange::Grid])
+    );
+    assert_eq!(manager.get_config().read().await.grid.cols, 40);
+
+    shutdown_tx.send(()).unwrap();
+    watcher.await.unwrap().unwrap();
+}
+
+#[test]
+fn test_config_diff() {
+    let old = Config::default();
+    assert!(old.diff(&old).is_empty());
+
+    let mut new = old.clone();
+    new.grid.rows = 6;
+    new.input.keyboard_device = Some("/dev/input/event3".to_string());
+    new.input.pending_timeout_ms = 500;
+    new.display.target_monitor = "DP-1".to_string();
+    assert_eq!(
+        old.diff(&new),
+        vec![
+            ConfigChange::Grid,
+            ConfigChange::KeyboardDevice,
+            ConfigChange::PendingTimeout,
+            ConfigChange::TargetMonitor,
+        ]
+    );
+
+    // Switching monitors re-places the cursor even when the grid stays the same
+    let mut monitor = old.clone();
+    monitor.display.target_monitor = "DP-1".to_string();
+    assert!(ConfigChange::repositions(&old.diff(&monitor)));
+    let mut timeout = old.clone();
+    timeout.input.pending_timeout_ms = 500;
+    assert!(!ConfigChange::repositions(&old.diff(&timeout)));
+}
+
+#[tokio::test]
+async fn test_overrides() {
+    let env = ConfigOverride::from_vars([
+        ("GRIDPOINTER_GRID_COLS".to_string(), "24".to_string()),
+        (
+            "GRIDPOINTER_MOVEMENT_MS_PER_CELL".to_string(),
+            "20".to_string(),
+        ),
+        (
+            "GRIDPOINTER_DISPLAY_TARGET_MONITOR".to_string(),
+            "HDMI-A-1".to_string(),
+        ),
+        (
+            "GRIDPOINTER_CONFIG".to_string(),
+            "/tmp/demo.toml".to_string(),
+        ),
+        ("GRIDPOINTER_GRID_COLOUR".to_string(), "red".to_string()),
+        ("HOME".to_string(), "/home/user".to_string()),
+    ]);
+    let keys: Vec<_> = env.iter().map(|setting| setting.key.as_str()).collect();
+    assert_eq!(
+        keys,
+        [
+            "display.target_monitor",
+            "grid.cols",
+            "movement.ms_per_cell"
+        ]
+    );
+
+    assert!("grid.cols".parse::<ConfigOverride>().is_err());
+    assert!("grid.colls=30".parse::<ConfigOverride>().is_err());
+    assert!("grid=30".parse::<ConfigOverride>().is_err());
+    assert!("marks.t.col=3".parse::<ConfigOverride>().is_ok());
+
+    // The file, then the environment, then --set
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+    fs::write(&path, "[grid]\ncols = 10\nrows = 8\n").unwrap();
+    let mut overrides = env;
+    overrides.push("grid.cols=30".parse().unwrap());
+    let manager = ConfigManager::open(path.clone(), None, overrides.clone())
+        .await
+        .unwrap();
+    let config = manager.get_config();
+    {
+        let config = config.read().await;
+        assert_eq!(config.grid.cols, 30);
+        assert_eq!(config.grid.rows, 8);
+        assert_eq!(config.movement.ms_per_cell, 20);
+        assert_eq!(config.display.target_monitor, "HDMI-A-1");
+    }
+
+    // Still applied after a reload
+    fs::write(&path, "[grid]\ncols = 12\nrows = 9\n").unwrap();
+    manager.reload().await.unwrap();
+    assert_eq!(config.read().await.grid.cols, 30);
+    assert_eq!(config.read().await.grid.rows, 9);
+
+    // Bad values are blamed on the override
+    let error = Config::from_toml_with("", &["grid.rows=1".parse().unwrap()]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Invalid configuration:\n  grid.rows: must be at least 2, got 1 (set by --set)"
+    );
+    assert!(Config::from_toml_with("", &["grid.rows=many".parse().unwrap()]).is_err());
+}
+
+#[test]
+fn test_partial_config_uses_defaults() {
+    let config = Config::from_toml("[grid]\ncols = 30\n").unwrap();
+    assert_eq!(config.grid.cols, 30);
+    assert_eq!(config.grid.rows, 12);
+    assert_eq!(config.movement.tween_ms, 150);
+    assert_eq!(config.display.target_monitor, "auto");
+
+    let config = Config::from_toml("").unwrap();
+    assert_eq!(config.grid.cols, 20);
+}
+
+#[test]
+fn test_unknown_keys() {
+    let content = r#"colour = "blue"
+
+[grid]
+cols = 30
+colums = 40
+
+[display]
+update_rate_hz = 120
+
+[marks]
+t = { col = 0, row = 0 }
+"#;
+
+    // Still loads, with the unknown keys reported
+    assert_eq!(Config::from_toml(content).unwrap().grid.cols, 30);
+    let unknown: Vec<_> = Config::unknown_keys(content)
+        .into_iter()
+        .map(|issue| issue.to_string())
+        .collect();
+    assert_eq!(
+        unknown,
+        [
+            "line 1, column 10: colour: unknown key, ignored",
+            "line 5, column 10: grid.colums: unknown key, ignored",
+        ]
+    );
+}
@@ -0,0 +1,171 @@
+//! Tests for the D-Bus interface against a private session bus
+
+#![cfg(feature = "dbus")]
+
+use futures_util::StreamExt;
+use gridpointer::dbus;
+use gridpointer::input::Direction;
+use gridpointer::ipc::{Command, Event, Request, Response};
+use serde_json::json;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Stdio};
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::sync::{broadcast, mpsc};
+
+#[zbus::proxy(
+    interface = "org.gridpointer.Daemon",
+    default_service = "org.gridpointer.Daemon",
+    default_path = "/org/gridpointer/Daemon"
+)]
+trait Daemon {
+    #[zbus(name = "Move")]
+    fn move_cells(&self, direction: &str, cells: u32) -> zbus::Result<()>;
+    fn jump(&self, col: u32, row: u32) -> zbus::Result<()>;
+    fn toggle(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn cell(&self) -> zbus::Result<(u32, u32)>;
+    #[zbus(property)]
+    fn output(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn active(&self) -> zbus::Result<bool>;
+    #[zbus(signal)]
+    fn config_reloaded(&self) -> zbus::Result<()>;
+}
+
+/// A `dbus-daemon --session` on a private socket, killed on drop
+struct SessionBus {
+    child: Child,
+    address: String,
+    _dir: TempDir,
+}
+
+impl SessionBus {
+    fn start() -> Option<Self> {
+        let dir = TempDir::new().unwrap();
+        let mut child = std::process::Command::new("dbus-daemon")
+            .arg("--session")
+            .arg("--nofork")
+            .arg("--print-address")
+            .arg(format!(
+                "--address=unix:path={}",
+                dir.path().join("bus").display()
+            ))
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+
+        let mut address = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+
+        Some(Self {
+            child,
+            address: address.trim().to_string(),
+            _dir: dir,
+        })
+    }
+
+    async fn connect(&self) -> zbus::Connection {
+        zbus::connection::Builder::address(self.address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap()
+    }
+}
+
+impl Drop for SessionBus {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[tokio::test]
+async fn test_dbus_interface() {
+    let Some(bus) = SessionBus::start() else {
+        eprintln!("dbus-daemon not available, skipping");
+        return;
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Command>();
+    let (events, _) = broadcast::channel(16);
+    let (_shutdown_tx, shutdown_rx) = broadcast::channel(1);
+    let (seen_tx, mut seen_rx) = mpsc::unbounded_channel();
+
+    // Stand-in for the main loop
+    tokio::spawn(async move {
+        while let Some((request, reply)) = rx.recv().await {
+            let response = match &request {
+                Request::Status => Response::data(json!({
+                    "active": true,
+                    "cell": { "col": 5, "row": 5 },
+                    "output": "DP-1",
+                })),
+                Request::Toggle => Response::data(json!({ "active": false })),
+                _ => Response::ok(),
+            };
+            let _ = seen_tx.send(request);
+            let _ = reply.send(response);
+        }
+    });
+
+    let server = bus.connect().await;
+    tokio::spawn(dbus::serve(server, tx, events.clone(), shutdown_rx));
+    assert_eq!(seen_rx.recv().await, Some(Request::Status));
+
+    let client = bus.connect().await;
+    let proxy = DaemonProxy::new(&client).await.unwrap();
+
+    // Wait for the name to be claimed
+    let mut cell = proxy.cell().await;
+    for _ in 0..50 {
+        if cell.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        cell = proxy.cell().await;
+    }
+    assert_eq!(cell.unwrap(), (5, 5));
+    assert_eq!(proxy.output().await.unwrap(), "DP-1");
+    assert!(proxy.active().await.unwrap());
+
+    // Methods are forwarded as control requests
+    proxy.jump(2, 3).await.unwrap();
+    assert_eq!(seen_rx.recv().await, Some(Request::Jump { col: 2, row: 3 }));
+    proxy.move_cells("Left", 4).await.unwrap();
+    assert_eq!(
+        seen_rx.recv().await,
+        Some(Request::Move {
+            direction: Direction::Left,
+            cells: 4
+        })
+    );
+    assert!(proxy.move_cells("sideways", 1).await.is_err());
+    assert!(!proxy.toggle().await.unwrap());
+
+    // Events update properties and emit signals
+    let mut cell_changes = proxy.receive_cell_changed().await;
+    let mut reloads = proxy.receive_config_reloaded().await.unwrap();
+    events.send(Event::CellChanged { col: 2, row: 3 }).unwrap();
+    events.send(Event::ConfigReloaded).unwrap();
+
+    let change = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let change = cell_changes.next().await.unwrap();
+            let value = change.get().await.unwrap();
+            if value != (5, 5) {
+                return value;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(change, (2, 3));
+    tokio::time::timeout(Duration::from_secs(5), reloads.next())
+        .await
+        .unwrap()
+        .unwrap();
+}
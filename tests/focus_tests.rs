@@ -0,0 +1,235 @@
+//! Tests for window rules and following Hyprland's focus events
+
+use gridpointer::config::{ConfigChange, ConfigManager};
+use gridpointer::focus::{self, FocusRule, FocusedWindow, Geometry, glob_match};
+use std::fs;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixListener;
+use tokio::sync::{broadcast, mpsc};
+
+fn window(app_id: &str, title: &str) -> FocusedWindow {
+    FocusedWindow {
+        app_id: app_id.to_string(),
+        title: title.to_string(),
+        geometry: None,
+    }
+}
+
+#[test]
+fn test_glob_match() {
+    assert!(glob_match("firefox", "firefox"));
+    assert!(!glob_match("firefox", "firefox-esr"));
+    assert!(glob_match("firefox*", "firefox-esr"));
+    assert!(glob_match("*Sheets*", "Budget - Google Sheets - Firefox"));
+    assert!(glob_match("*.rs - *", "main.rs - gridpointer"));
+    assert!(glob_match("fire?ox", "firefox"));
+    assert!(glob_match("*", ""));
+    assert!(!glob_match("?", ""));
+    assert!(!glob_match("*Sheets", "Sheets - Firefox"));
+}
+
+#[test]
+fn test_rules() {
+    let rules = [
+        FocusRule {
+            app_id: Some("firefox".to_string()),
+            title: Some("*Google Sheets*".to_string()),
+            profile: "spreadsheet".to_string(),
+        },
+        FocusRule {
+            app_id: Some("jetbrains-*".to_string()),
+            title: None,
+            profile: "ide".to_string(),
+        },
+        FocusRule {
+            app_id: None,
+            title: None,
+            profile: "never".to_string(),
+        },
+    ];
+
+    let profile = |app_id, title| focus::profile_for(&rules, &window(app_id, title));
+    assert_eq!(
+        profile("firefox", "Budget - Google Sheets"),
+        Some("spreadsheet")
+    );
+    assert_eq!(profile("firefox", "Rust docs"), None);
+    assert_eq!(profile("jetbrains-idea", "Main.java"), Some("ide"));
+    assert_eq!(profile("", ""), None);
+
+    assert_eq!(
+        focus::parse_event("activewindow>>kitty,vim a, b"),
+        Some(window("kitty", "vim a, b"))
+    );
+    assert_eq!(focus::parse_event("activewindow>>,"), Some(window("", "")));
+    assert_eq!(focus::parse_event("workspace>>2"), None);
+}
+
+#[tokio::test]
+async fn test_hyprland_focus_events() {
+    let dir = TempDir::new().unwrap();
+    let requests = UnixListener::bind(dir.path().join(".socket.sock")).unwrap();
+    let events = UnixListener::bind(dir.path().join(".socket2.sock")).unwrap();
+
+    // Answers to the queries made at start and after each window event
+    let replies = [
+        r#"{"class": "kitty", "title": "~", "at": [0, 0], "size": [960, 1080]}"#,
+        r#"{"class": "firefox", "title": "Rust docs", "at": [960, 0], "size": [960, 1080]}"#,
+        r#"{"class": "firefox", "title": "Rust docs", "at": [960, 0], "size": [960, 1080]}"#,
+        r#"{"class": "firefox", "title": "Rust docs", "at": [100, 50], "size": [800, 600]}"#,
+        r#"{"class": "firefox", "title": "Rust book", "at": [100, 50], "size": [800, 600]}"#,
+        r#"{"class": "firefox", "title": "Google Sheets", "at": [100, 50], "size": [800, 600]}"#,
+        r#"{"class": "firefox", "title": "Google Sheets", "at": [100, 72], "size": [800, 578]}"#,
+    ];
+    tokio::spawn(async move {
+        for reply in replies {
+            let (mut stream, _) = requests.accept().await.unwrap();
+            let mut request = [0; 64];
+            let len = stream.read(&mut request).await.unwrap();
+            assert_eq!(&request[..len], b"j/activewindow");
+            stream.write_all(reply.as_bytes()).await.unwrap();
+        }
+    });
+    tokio::spawn(async move {
+        let (mut stream, _) = events.accept().await.unwrap();
+        stream
+            .write_all(
+                b"workspace>>2\nactivewindow>>firefox,Rust docs\nactivewindowv2>>5678\n\
+                  fullscreen>>0\nmovewindow>>5678,2\nwindowtitle>>5678\n\
+                  windowtitlev2>>5678,Google Sheets\ntogglegroup>>1,5678\n",
+            )
+            .await
+            .unwrap();
+        // Hold the connection open until the test is done
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    });
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+    let path = dir.path().to_path_buf();
+    let handle = tokio::spawn(async move { focus::run_hyprland(&path, tx, shutdown_rx).await });
+
+    let geometry = |x, y, width, height| {
+        Some(Geometry {
+            x,
+            y,
+            width,
+            height,
+        })
+    };
+    let kitty = rx.recv().await.unwrap();
+    assert_eq!(
+        (kitty.app_id.as_str(), kitty.geometry),
+        ("kitty", geometry(0, 0, 960, 1080))
+    );
+    let firefox = rx.recv().await.unwrap();
+    assert_eq!(firefox.title, "Rust docs");
+    assert_eq!(firefox.geometry, geometry(960, 0, 960, 1080));
+    // Going fullscreen changed nothing here, so the next is the move
+    let moved = rx.recv().await.unwrap();
+    assert_eq!(moved.geometry, geometry(100, 50, 800, 600));
+    // Title changes can switch profiles, so they are followed too
+    assert_eq!(rx.recv().await.unwrap().title, "Rust book");
+    assert_eq!(rx.recv().await.unwrap().title, "Google Sheets");
+    // Grouping makes room for the group bar
+    let grouped = rx.recv().await.unwrap();
+    assert_eq!(grouped.geometry, geometry(100, 72, 800, 578));
+
+    shutdown_tx.send(()).unwrap();
+    handle.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_focused_window_query() {
+    let dir = TempDir::new().unwrap();
+    let requests = UnixListener::bind(dir.path().join(".socket.sock")).unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = requests.accept().await.unwrap();
+        let mut request = [0; 64];
+        let len = stream.read(&mut request).await.unwrap();
+        assert_eq!(&request[..len], b"j/activewindow");
+        stream
+            .write_all(br#"{"class": "kitty", "title": "~", "at": [0, 0], "size": [960, 1080]}"#)
+            .await
+            .unwrap();
+        drop(stream);
+
+        // A wedged compositor accepts but never answers
+        let (_stream, _) = requests.accept().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    });
+
+    let window = focus::hyprland_focused_window(dir.path()).await.unwrap();
+    assert_eq!(window.app_id, "kitty");
+
+    let asked = tokio::time::Instant::now();
+    assert_eq!(focus::hyprland_focused_window(dir.path()).await, None);
+    assert!(asked.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn test_window_profile() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+    fs::write(
+        &path,
+        r#"
+[grid]
+cols = 20
+
+[[rules]]
+app_id = "libreoffice-calc"
+profile = "spreadsheet"
+
+[profile.spreadsheet.grid]
+cols = 40
+
+[profile.gaming.grid]
+cols = 8
+"#,
+    )
+    .unwrap();
+    let manager = ConfigManager::open(path.clone(), Some("gaming".to_string()), Vec::new())
+        .await
+        .unwrap();
+    let config = manager.get_config();
+    assert_eq!(config.read().await.rules.len(), 1);
+
+    // The window's profile wins over the chosen one while it has focus
+    let mut reloads = manager.subscribe_reloads();
+    let changes = manager
+        .set_window_profile(Some("spreadsheet".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(changes, vec![ConfigChange::Grid]);
+    assert_eq!(reloads.recv().await.unwrap(), vec![ConfigChange::Grid]);
+    assert_eq!(config.read().await.grid.cols, 40);
+    assert_eq!(manager.profile().await.as_deref(), Some("spreadsheet"));
+
+    // Same profile again changes nothing
+    let changes = manager
+        .set_window_profile(Some("spreadsheet".to_string()))
+        .await
+        .unwrap();
+    assert!(changes.is_empty());
+
+    // Focus elsewhere goes back to the chosen profile
+    manager.set_window_profile(None).await.unwrap();
+    assert_eq!(config.read().await.grid.cols, 8);
+    assert_eq!(manager.profile().await.as_deref(), Some("gaming"));
+
+    // Rules must name a profile that exists
+    fs::write(
+        &path,
+        "[[rules]]\napp_id = \"firefox\"\nprofile = \"browser\"\n\n[profile.gaming]\n",
+    )
+    .unwrap();
+    let error = format!("{:#}", manager.reload().await.unwrap_err());
+    assert!(
+        error.contains("rules.0.profile: no profile named browser"),
+        "{}",
+        error
+    );
+}
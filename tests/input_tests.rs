@@ -0,0 +1,184 @@
+//! Tests for the keyboard command grammar and device reconnects
+
+use evdev::Key;
+use gridpointer::{
+    input::{Direction, Hotplug, InputEvent, KeyParser, Operator, Reconnect},
+    motion::{JumpTarget, MotionEvent},
+};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Feed keys at the same instant, returning the last event produced
+fn feed(parser: &mut KeyParser, keys: &[Key]) -> Option<InputEvent> {
+    let now = Instant::now();
+    keys.iter()
+        .fold(None, |_, key| parser.feed(*key, false, now))
+}
+
+fn motion(motion: MotionEvent, operator: Option<Operator>) -> Option<InputEvent> {
+    Some(InputEvent::Motion { motion, operator })
+}
+
+#[test]
+fn test_counted_moves() {
+    let mut parser = KeyParser::new(Duration::from_secs(1));
+
+    assert_eq!(
+        feed(&mut parser, &[Key::KEY_L]),
+        motion(
+            MotionEvent::Move {
+                direction: Direction::Right
+            },
+            None
+        )
+    );
+    assert_eq!(
+        feed(&mut parser, &[Key::KEY_1, Key::KEY_2, Key::KEY_J]),
+        motion(
+            MotionEvent::MoveBy {
+                direction: Direction::Down,
+                cells: 12
+            },
+            None
+        )
+    );
+    assert_eq!(
+        feed(&mut parser, &[Key::KEY_1, Key::KEY_2, Key::KEY_BACKSLASH]),
+        motion(
+            MotionEvent::Jump {
+                target: JumpTarget::Column(11)
+            },
+            None
+        )
+    );
+    assert_eq!(
+        feed(&mut parser, &[Key::KEY_0]),
+        motion(
+            MotionEvent::Jump {
+                target: JumpTarget::RowStart
+            },
+            None
+        )
+    );
+}
+
+#[test]
+fn test_absolute_jumps() {
+    let mut parser = KeyParser::new(Duration::from_secs(1));
+    let jump = |target| motion(MotionEvent::Jump { target }, None);
+
+    // Counts are one-based positions for `|` and `G`, and `10` is a count, not `1` then `0`
+    assert_eq!(
+        feed(&mut parser, &[Key::KEY_1, Key::KEY_0, Key::KEY_BACKSLASH]),
+        jump(JumpTarget::Column(9))
+    );
+    assert_eq!(
+        feed(&mut parser, &[Key::KEY_5, Key::KEY_G]),
+        jump(JumpTarget::Row(4))
+    );
+    assert_eq!(
+        feed(&mut parser, &[Key::KEY_BACKSLASH]),
+        jump(JumpTarget::RowStart)
+    );
+    assert_eq!(
+        feed(&mut parser, &[Key::KEY_G]),
+        jump(JumpTarget::ColumnEnd)
+    );
+}
+
+#[test]
+fn test_operators() {
+    let mut parser = KeyParser::new(Duration::from_secs(1));
+
+    // Counts before and after the operator multiply
+    assert_eq!(
+        feed(&mut parser, &[Key::KEY_2, Key::KEY_D, Key::KEY_3]),
+        None
+    );
+    assert_eq!(parser.pending().as_deref(), Some("2d3"));
+    assert_eq!(
+        feed(&mut parser, &[Key::KEY_L]),
+        motion(
+            MotionEvent::MoveBy {
+                direction: Direction::Right,
+                cells: 6
+            },
+            Some(Operator::Drag)
+        )
+    );
+    assert_eq!(parser.pending(), None);
+
+    assert_eq!(
+        feed(&mut parser, &[Key::KEY_C, Key::KEY_KP5]),
+        motion(
+            MotionEvent::Jump {
+                target: JumpTarget::Center
+            },
+            Some(Operator::Click)
+        )
+    );
+
+    // A key that isn't a motion cancels the operator
+    assert_eq!(feed(&mut parser, &[Key::KEY_D, Key::KEY_ESC]), None);
+    assert_eq!(parser.pending(), None);
+}
+
+#[test]
+fn test_marks_and_timeout() {
+    let mut parser = KeyParser::new(Duration::from_millis(500));
+    let start = Instant::now();
+
+    assert_eq!(parser.feed(Key::KEY_M, false, start), None);
+    assert_eq!(parser.pending().as_deref(), Some("m"));
+    assert_eq!(
+        parser.feed(Key::KEY_A, false, start),
+        Some(InputEvent::SetMark { key: 'a' })
+    );
+    assert_eq!(parser.deadline(), None);
+
+    // An incomplete count is dropped after the timeout
+    assert_eq!(parser.feed(Key::KEY_5, false, start), None);
+    assert_eq!(parser.deadline(), Some(start + Duration::from_millis(500)));
+    assert!(!parser.expire(start + Duration::from_millis(100)));
+    assert!(parser.expire(start + Duration::from_millis(600)));
+    assert_eq!(
+        parser.feed(Key::KEY_L, false, start + Duration::from_millis(700)),
+        motion(
+            MotionEvent::Move {
+                direction: Direction::Right
+            },
+            None
+        )
+    );
+}
+
+#[test]
+fn test_reconnect_policy() {
+    // An unconfigured gamepad absent at startup is never waited for
+    let gamepad = Reconnect::new(None, false);
+    assert!(!gamepad.armed(false));
+
+    // One that was auto-detected is detected again after unplugging
+    let gamepad = Reconnect::new(None, true);
+    assert!(gamepad.detected);
+    assert!(gamepad.armed(false));
+    assert!(!gamepad.armed(true));
+
+    // Configured paths are always retried while missing
+    let keyboard = Reconnect::new(Some(PathBuf::from("/dev/input/event3")), false);
+    assert!(!keyboard.detected);
+    assert!(keyboard.armed(false));
+    assert!(!keyboard.armed(true));
+}
+
+#[tokio::test]
+async fn test_hotplug_watch() {
+    let dir = TempDir::new().unwrap();
+    let mut hotplug = Hotplug::watch(dir.path()).unwrap();
+
+    std::fs::write(dir.path().join("event7"), "").unwrap();
+    tokio::time::timeout(Duration::from_secs(5), hotplug.changed())
+        .await
+        .expect("new device node was not reported");
+}
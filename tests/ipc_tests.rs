@@ -0,0 +1,171 @@
+//! Tests for the control socket protocol
+
+use gridpointer::input::{DeviceKind, Direction};
+use gridpointer::ipc::{self, Event, IpcServer, Request, Response};
+use gridpointer::wl::Button;
+use serde_json::json;
+use tempfile::TempDir;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{broadcast, mpsc};
+
+#[test]
+fn test_request_parsing() {
+    let request: Request =
+        serde_json::from_str(r#"{"command": "move", "direction": "left", "cells": 3}"#).unwrap();
+    assert_eq!(
+        request,
+        Request::Move {
+            direction: Direction::Left,
+            cells: 3
+        }
+    );
+
+    // Optional fields fall back to defaults
+    let request: Request =
+        serde_json::from_str(r#"{"command": "move", "direction": "up"}"#).unwrap();
+    assert_eq!(
+        request,
+        Request::Move {
+            direction: Direction::Up,
+            cells: 1
+        }
+    );
+    let request: Request = serde_json::from_str(r#"{"command": "click"}"#).unwrap();
+    assert_eq!(
+        request,
+        Request::Click {
+            button: Button::Left
+        }
+    );
+    let request: Request = serde_json::from_str(r#"{"command": "scroll", "y": -2}"#).unwrap();
+    assert_eq!(request, Request::Scroll { x: 0, y: -2 });
+
+    let request: Request =
+        serde_json::from_str(r#"{"command": "jump", "col": 4, "row": 7}"#).unwrap();
+    assert_eq!(request, Request::Jump { col: 4, row: 7 });
+    let request: Request = serde_json::from_str(r#"{"command": "status"}"#).unwrap();
+    assert_eq!(request, Request::Status);
+
+    assert!(serde_json::from_str::<Request>(r#"{"command": "teleport"}"#).is_err());
+}
+
+#[tokio::test]
+async fn test_socket_round_trip() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("gridpointer.sock");
+
+    let server = IpcServer::bind(path.clone()).await.unwrap();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+    let (events, _) = broadcast::channel(16);
+    let server_handle = tokio::spawn(server.run(tx, events, shutdown_rx));
+
+    // Stand-in for the main loop: answer cell queries and acknowledge the rest
+    tokio::spawn(async move {
+        while let Some((request, reply)) = rx.recv().await {
+            let response = match request {
+                Request::Cell => Response::data(json!({ "col": 5, "row": 5 })),
+                _ => Response::ok(),
+            };
+            let _ = reply.send(response);
+        }
+    });
+
+    let response = ipc::send_request(&path, &Request::Cell).await.unwrap();
+    assert_eq!(response.data, Some(json!({ "col": 5, "row": 5 })));
+
+    let response = ipc::send_request(
+        &path,
+        &Request::Dash {
+            direction: Direction::Right,
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(response, Response::ok());
+
+    // A second server refuses to take over a live socket
+    assert!(IpcServer::bind(path.clone()).await.is_err());
+
+    // The socket file is removed on shutdown
+    shutdown_tx.send(()).unwrap();
+    server_handle.await.unwrap().unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn test_invalid_request() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("gridpointer.sock");
+
+    let server = IpcServer::bind(path.clone()).await.unwrap();
+    let (tx, _rx) = mpsc::unbounded_channel();
+    let (_shutdown_tx, shutdown_rx) = broadcast::channel(1);
+    let (events, _) = broadcast::channel(16);
+    tokio::spawn(server.run(tx, events, shutdown_rx));
+
+    let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    writer.write_all(b"not json\n").await.unwrap();
+    let line = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await
+        .unwrap()
+        .unwrap();
+
+    let response: Response = serde_json::from_str(&line).unwrap();
+    assert!(!response.ok);
+    assert!(response.error.unwrap().starts_with("Invalid request"));
+}
+
+#[tokio::test]
+async fn test_subscribe() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("gridpointer.sock");
+
+    let server = IpcServer::bind(path.clone()).await.unwrap();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (_shutdown_tx, shutdown_rx) = broadcast::channel(1);
+    let (events, _) = broadcast::channel(16);
+    tokio::spawn(server.run(tx, events.clone(), shutdown_rx));
+
+    tokio::spawn(async move {
+        while let Some((request, reply)) = rx.recv().await {
+            assert_eq!(request, Request::Status);
+            let _ = reply.send(Response::data(json!({ "active": true })));
+        }
+    });
+
+    // The first line is the current status
+    let mut lines = ipc::subscribe(&path).await.unwrap();
+    let status: Response =
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(status.data, Some(json!({ "active": true })));
+
+    // Then every published event, in order
+    events.send(Event::CellChanged { col: 2, row: 7 }).unwrap();
+    events
+        .send(Event::DeviceDisconnected {
+            device: DeviceKind::Gamepad,
+            name: "Pad".to_string(),
+        })
+        .unwrap();
+    events.send(Event::ConfigReloaded).unwrap();
+
+    let line = lines.next_line().await.unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&line).unwrap(),
+        json!({ "event": "cell_changed", "col": 2, "row": 7 })
+    );
+    let line = lines.next_line().await.unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&line).unwrap(),
+        json!({ "event": "device_disconnected", "device": "gamepad", "name": "Pad" })
+    );
+    let line = lines.next_line().await.unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_str::<Event>(&line).unwrap(),
+        Event::ConfigReloaded
+    );
+}
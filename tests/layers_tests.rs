@@ -0,0 +1,173 @@
+//! Tests for included files and profiles
+
+use gridpointer::config::{ConfigChange, ConfigManager};
+use gridpointer::error::GridPointerError;
+use gridpointer::layers::Layers;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn load(path: &Path) -> gridpointer::Result<Layers> {
+    Layers::load(path, &fs::read_to_string(path).unwrap())
+}
+
+#[test]
+fn test_includes_and_profiles() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join("shared")).unwrap();
+    fs::write(
+        dir.path().join("shared/base.toml"),
+        r#"
+[grid]
+cols = 20
+rows = 10
+
+[movement]
+tween_ms = 200
+
+[profile.gaming.grid]
+cols = 8
+rows = 4
+"#,
+    )
+    .unwrap();
+    let path = dir.path().join("config.toml");
+    fs::write(
+        &path,
+        r#"
+include = ["shared/base.toml"]
+
+[grid]
+cols = 30
+
+[profile.gaming.movement]
+tween_ms = 50
+
+[profile.coding.movement]
+duration_mode = "per_cell"
+"#,
+    )
+    .unwrap();
+
+    let layers = load(&path).unwrap();
+    assert_eq!(layers.sources().len(), 2);
+    assert_eq!(layers.profiles().collect::<Vec<_>>(), ["coding", "gaming"]);
+
+    // The including file wins over what it includes
+    let base = layers.build(None, &[]).unwrap();
+    assert_eq!((base.grid.cols, base.grid.rows), (30, 10));
+    assert_eq!(base.movement.tween_ms, 200);
+
+    // Profiles from both files merge, and win over the base
+    let gaming = layers.build(Some("gaming"), &[]).unwrap();
+    assert_eq!((gaming.grid.cols, gaming.grid.rows), (8, 4));
+    assert_eq!(gaming.movement.tween_ms, 50);
+
+    // ...but not over overrides
+    let overrides = ["grid.cols=12".parse().unwrap()];
+    assert_eq!(
+        layers.build(Some("gaming"), &overrides).unwrap().grid.cols,
+        12
+    );
+
+    let error = layers.build(Some("typing"), &[]).unwrap_err();
+    assert!(error.to_string().contains("coding, gaming"), "{}", error);
+    assert!(layers.unknown_keys().is_empty());
+}
+
+#[test]
+fn test_issues_are_located_in_their_file() {
+    let dir = TempDir::new().unwrap();
+    let base = dir.path().join("base.toml");
+    fs::write(&base, "[grid]\ncols = 1\n").unwrap();
+    let path = dir.path().join("config.toml");
+    fs::write(
+        &path,
+        "include = [\"base.toml\"]\n\n[profile.tiny.grid]\nrows = 1\ncolour = \"red\"\n",
+    )
+    .unwrap();
+    let layers = load(&path).unwrap();
+
+    let Err(GridPointerError::InvalidConfig(issues)) = layers.build(Some("tiny"), &[]) else {
+        panic!("expected validation issues");
+    };
+    let issues: Vec<_> = issues.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        issues,
+        [
+            format!(
+                "line 2, column 8: grid.cols: must be at least 2, got 1 (in {})",
+                base.display()
+            ),
+            "line 4, column 8: profile.tiny.grid.rows: must be at least 2, got 1".to_string(),
+        ]
+    );
+
+    let unknown: Vec<_> = layers
+        .unknown_keys()
+        .into_iter()
+        .map(|(file, issue)| (file.to_path_buf(), issue.key))
+        .collect();
+    assert_eq!(unknown, [(path, "profile.tiny.grid.colour".to_string())]);
+}
+
+#[test]
+fn test_include_cycle() {
+    let dir = TempDir::new().unwrap();
+    let a = dir.path().join("a.toml");
+    let b = dir.path().join("b.toml");
+    fs::write(&a, "include = [\"b.toml\"]\n").unwrap();
+    fs::write(&b, "include = [\"a.toml\"]\n").unwrap();
+    let error = load(&a).unwrap_err().to_string();
+    assert!(error.contains("included by itself"), "{}", error);
+
+    fs::write(&a, "include = [\"missing.toml\"]\n").unwrap();
+    let error = load(&a).unwrap_err().to_string();
+    assert!(error.contains("Cannot include"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_switch_profile() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+    fs::write(
+        &path,
+        "[grid]\ncols = 20\n\n[profile.gaming.grid]\ncols = 8\n\n[profile.broken.grid]\ncols = 0\n",
+    )
+    .unwrap();
+
+    let manager = ConfigManager::open(path.clone(), Some("gaming".to_string()), Vec::new())
+        .await
+        .unwrap();
+    let config = manager.get_config();
+    assert_eq!(config.read().await.grid.cols, 8);
+
+    // The selection survives a reload
+    manager.reload().await.unwrap();
+    assert_eq!(config.read().await.grid.cols, 8);
+
+    let mut reloads = manager.subscribe_reloads();
+    assert_eq!(
+        manager.set_profile(None).await.unwrap(),
+        vec![ConfigChange::Grid]
+    );
+    assert_eq!(reloads.recv().await.unwrap(), vec![ConfigChange::Grid]);
+    assert_eq!(config.read().await.grid.cols, 20);
+    assert_eq!(manager.profile().await, None);
+
+    // A profile that doesn't load isn't switched to
+    assert!(
+        manager
+            .set_profile(Some("broken".to_string()))
+            .await
+            .is_err()
+    );
+    assert!(
+        manager
+            .set_profile(Some("typing".to_string()))
+            .await
+            .is_err()
+    );
+    assert_eq!(manager.profile().await, None);
+    assert_eq!(config.read().await.grid.cols, 20);
+}
@@ -0,0 +1,74 @@
+//! Tests for the single-instance lock
+
+use gridpointer::error::GridPointerError;
+use gridpointer::ipc::{IpcServer, Request, Response};
+use gridpointer::lock::InstanceLock;
+use tempfile::TempDir;
+use tokio::sync::{broadcast, mpsc};
+
+#[test]
+fn test_second_instance_is_refused() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("gridpointer.lock");
+
+    let lock = InstanceLock::acquire(path.clone()).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap().trim(),
+        std::process::id().to_string()
+    );
+
+    // The error names the PID holding the lock
+    match InstanceLock::acquire(path.clone()) {
+        Err(GridPointerError::AlreadyRunning { pid }) => {
+            assert_eq!(pid, Some(std::process::id()));
+        }
+        other => panic!("expected AlreadyRunning, got {:?}", other),
+    }
+
+    // Released on drop
+    drop(lock);
+    InstanceLock::acquire(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_replace_running_instance() {
+    let dir = TempDir::new().unwrap();
+    let lock_path = dir.path().join("gridpointer.lock");
+    let socket_path = dir.path().join("gridpointer.sock");
+
+    // A stand-in instance that releases its lock when asked to quit
+    let lock = InstanceLock::acquire(lock_path.clone()).unwrap();
+    let server = IpcServer::bind(socket_path.clone()).await.unwrap();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (events, _) = broadcast::channel(16);
+    let (_shutdown_tx, shutdown_rx) = broadcast::channel(1);
+    tokio::spawn(server.run(tx, events, shutdown_rx));
+    tokio::spawn(async move {
+        let mut lock = Some(lock);
+        while let Some((request, reply)) = rx.recv().await {
+            if request == Request::Quit {
+                lock.take();
+            }
+            let _ = reply.send(Response::ok());
+        }
+    });
+
+    let _replacement = InstanceLock::replace(lock_path.clone(), &socket_path)
+        .await
+        .unwrap();
+    assert!(matches!(
+        InstanceLock::acquire(lock_path),
+        Err(GridPointerError::AlreadyRunning { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_replace_without_running_instance() {
+    let dir = TempDir::new().unwrap();
+    let lock_path = dir.path().join("gridpointer.lock");
+
+    // Nothing to replace, so no control socket is needed
+    InstanceLock::replace(lock_path, &dir.path().join("missing.sock"))
+        .await
+        .unwrap();
+}
@@ -0,0 +1,53 @@
+//! Tests for mark storage
+
+use gridpointer::marks::{Mark, MarkStore};
+use std::collections::BTreeMap;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_marks_persist() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("gridpointer").join("marks.toml");
+
+    let mut store = MarkStore::load(path.clone(), &BTreeMap::new()).await;
+    assert_eq!(store.get('a'), None);
+
+    let mark = Mark {
+        col: 3,
+        row: 4,
+        output: Some("DP-1".to_string()),
+    };
+    store.set('a', mark.clone()).await.unwrap();
+    assert_eq!(store.get('a'), Some(&mark));
+
+    // A fresh store reads the mark back from the state file
+    let reloaded = MarkStore::load(path, &BTreeMap::new()).await;
+    assert_eq!(reloaded.get('a'), Some(&mark));
+}
+
+#[tokio::test]
+async fn test_predefined_marks() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("marks.toml");
+
+    let toolbar = Mark {
+        col: 0,
+        row: 0,
+        output: None,
+    };
+    let mut predefined = BTreeMap::new();
+    predefined.insert("t".to_string(), toolbar.clone());
+    predefined.insert("toolong".to_string(), toolbar.clone());
+
+    let mut store = MarkStore::load(path, &predefined).await;
+    assert_eq!(store.get('t'), Some(&toolbar));
+
+    // Runtime marks take precedence over predefined ones
+    let send = Mark {
+        col: 18,
+        row: 11,
+        output: None,
+    };
+    store.set('t', send.clone()).await.unwrap();
+    assert_eq!(store.get('t'), Some(&send));
+}
@@ -0,0 +1,45 @@
+//! Tests for config layout migrations
+
+use gridpointer::config::ConfigManager;
+use gridpointer::migrate::{self, CONFIG_VERSION};
+use tempfile::TempDir;
+
+const UNVERSIONED: &str = r#"# My grid
+[grid]
+cols = 30  # wide monitor
+rows = 12
+"#;
+
+#[test]
+fn test_unchanged_layout_is_left_alone() {
+    // No version key means version 0, whose upgrade so far only lacked the key
+    assert!(migrate::upgrade(UNVERSIONED).unwrap().is_none());
+    let content = format!("version = 0\n{}", UNVERSIONED);
+    assert!(migrate::upgrade(&content).unwrap().is_none());
+    let content = format!("version = {}\n{}", CONFIG_VERSION, UNVERSIONED);
+    assert!(migrate::upgrade(&content).unwrap().is_none());
+}
+
+#[test]
+fn test_newer_version_is_refused() {
+    let content = format!("version = {}\n", CONFIG_VERSION + 1);
+    let error = migrate::upgrade(&content).unwrap_err().to_string();
+    assert!(error.contains("newer GridPointer"), "{}", error);
+    assert!(migrate::upgrade("version = \"one\"\n").is_err());
+}
+
+#[tokio::test]
+async fn test_load_does_not_rewrite_unchanged_file() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, UNVERSIONED).unwrap();
+
+    let manager = ConfigManager::with_path(path.clone()).await.unwrap();
+    let config = manager.get_config();
+    assert_eq!(config.read().await.grid.cols, 30);
+    assert_eq!(config.read().await.version, CONFIG_VERSION);
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), UNVERSIONED);
+    let files: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+    assert_eq!(files.len(), 1, "no backup is written");
+}
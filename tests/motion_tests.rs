@@ -1,107 +1,133 @@
 //! Tests for motion controller
 
 use gridpointer::{
-    config::{Config, DisplayConfig, GridConfig, InputConfig, MovementConfig},
+    config::{CellAnchor, Config, DisplayConfig, GridConfig, InputConfig, MovementConfig},
+    focus::Geometry,
     input::Direction,
-    motion::{MotionController, MotionEvent},
+    motion::{JumpTarget, ManualClock, MotionController, MotionEvent, Region},
 };
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
 #[tokio::test]
 async fn test_basic_movement() {
     let config = Config {
-        grid: GridConfig { cols: 10, rows: 10 },
+        grid: GridConfig {
+            cols: 10,
+            rows: 10,
+            ..Default::default()
+        },
         movement: MovementConfig {
             dash_cells: 3,
             tween_ms: 100,
+            ..Default::default()
         },
         input: InputConfig {
             keyboard_device: None,
             gamepad_device: None,
+            ..Default::default()
         },
         display: DisplayConfig {
             target_monitor: "auto".to_string(),
+            ..Default::default()
         },
+        ..Default::default()
     };
 
     let mut controller = MotionController::new(Arc::new(RwLock::new(config)));
 
     // Start at center
-    assert_eq!(controller.current_grid_pos, (0, 0));
+    assert_eq!(controller.grid_position(), (5, 5));
 
     // Move right
     controller.handle_event(MotionEvent::Move {
         direction: Direction::Right,
     });
-    assert_eq!(controller.current_grid_pos, (1, 0));
+    assert_eq!(controller.grid_position(), (6, 5));
 
     // Move down
     controller.handle_event(MotionEvent::Move {
         direction: Direction::Down,
     });
-    assert_eq!(controller.current_grid_pos, (1, 1));
+    assert_eq!(controller.grid_position(), (6, 6));
 
     // Move left
     controller.handle_event(MotionEvent::Move {
         direction: Direction::Left,
     });
-    assert_eq!(controller.current_grid_pos, (0, 1));
+    assert_eq!(controller.grid_position(), (5, 6));
 
     // Move up
     controller.handle_event(MotionEvent::Move {
         direction: Direction::Up,
     });
-    assert_eq!(controller.current_grid_pos, (0, 0));
+    assert_eq!(controller.grid_position(), (5, 5));
 }
 
 #[tokio::test]
 async fn test_dash_movement() {
     let config = Config {
-        grid: GridConfig { cols: 20, rows: 20 },
+        grid: GridConfig {
+            cols: 20,
+            rows: 20,
+            ..Default::default()
+        },
         movement: MovementConfig {
             dash_cells: 5,
             tween_ms: 100,
+            ..Default::default()
         },
         input: InputConfig {
             keyboard_device: None,
             gamepad_device: None,
+            ..Default::default()
         },
         display: DisplayConfig {
             target_monitor: "auto".to_string(),
+            ..Default::default()
         },
+        ..Default::default()
     };
 
     let mut controller = MotionController::new(Arc::new(RwLock::new(config)));
 
-    // Dash right 5 cells
+    // Dash right 5 cells from the center
     controller.handle_event(MotionEvent::Dash {
         direction: Direction::Right,
     });
-    assert_eq!(controller.current_grid_pos, (5, 0));
+    assert_eq!(controller.grid_position(), (15, 10));
 
     // Dash down 5 cells
     controller.handle_event(MotionEvent::Dash {
         direction: Direction::Down,
     });
-    assert_eq!(controller.current_grid_pos, (5, 5));
+    assert_eq!(controller.grid_position(), (15, 15));
 }
 
 #[tokio::test]
 async fn test_boundary_conditions() {
     let config = Config {
-        grid: GridConfig { cols: 5, rows: 5 },
+        grid: GridConfig {
+            cols: 5,
+            rows: 5,
+            ..Default::default()
+        },
         movement: MovementConfig {
             dash_cells: 10,
             tween_ms: 100,
+            ..Default::default()
         },
         input: InputConfig {
             keyboard_device: None,
             gamepad_device: None,
+            ..Default::default()
         },
         display: DisplayConfig {
             target_monitor: "auto".to_string(),
+            ..Default::default()
         },
+        ..Default::default()
     };
 
     let mut controller = MotionController::new(Arc::new(RwLock::new(config)));
@@ -110,14 +136,443 @@ async fn test_boundary_conditions() {
     controller.handle_event(MotionEvent::Dash {
         direction: Direction::Right,
     });
-    assert_eq!(controller.current_grid_pos, (4, 0)); // Clamped to max
+    assert_eq!(controller.grid_position(), (4, 2)); // Clamped to max
 
     // Try to move left beyond boundary
-    controller.current_grid_pos = (0, 0);
+    controller.handle_event(MotionEvent::JumpTo { cell: (0, 0) });
     controller.handle_event(MotionEvent::Move {
         direction: Direction::Left,
     });
-    assert_eq!(controller.current_grid_pos, (0, 0)); // Should stay at 0
+    assert_eq!(controller.grid_position(), (0, 0)); // Should stay at 0
+}
+
+#[tokio::test]
+async fn test_edge_modes() {
+    use gridpointer::config::{EdgeMode, Overshoot};
+
+    let config = Config {
+        grid: GridConfig {
+            cols: 5,
+            rows: 5,
+            edge_x: EdgeMode::Wrap,
+            edge_y: EdgeMode::Clamp,
+            overshoot: Overshoot::Refuse,
+            ..Default::default()
+        },
+        movement: MovementConfig {
+            dash_cells: 3,
+            tween_ms: 100,
+            ..Default::default()
+        },
+        input: InputConfig {
+            keyboard_device: None,
+            gamepad_device: None,
+            ..Default::default()
+        },
+        display: DisplayConfig {
+            target_monitor: "auto".to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut controller = MotionController::new(Arc::new(RwLock::new(config)));
+    controller.handle_event(MotionEvent::Jump {
+        target: JumpTarget::TopLeft,
+    });
+
+    // Left from the first column wraps to the last
+    controller.handle_event(MotionEvent::Move {
+        direction: Direction::Left,
+    });
+    assert_eq!(controller.grid_position(), (4, 0));
+
+    // Dashing right wraps around past the edge
+    controller.handle_event(MotionEvent::Dash {
+        direction: Direction::Right,
+    });
+    assert_eq!(controller.grid_position(), (2, 0));
+
+    // Vertical axis clamps, and refuses a dash that would overshoot
+    controller.handle_event(MotionEvent::Dash {
+        direction: Direction::Down,
+    });
+    assert_eq!(controller.grid_position(), (2, 3));
+    controller.handle_event(MotionEvent::Dash {
+        direction: Direction::Down,
+    });
+    assert_eq!(controller.grid_position(), (2, 3));
+}
+
+#[tokio::test]
+async fn test_next_output_handoff() {
+    use gridpointer::config::EdgeMode;
+
+    let config = Config {
+        grid: GridConfig {
+            cols: 5,
+            rows: 5,
+            edge_x: EdgeMode::NextOutput,
+            ..Default::default()
+        },
+        movement: MovementConfig {
+            dash_cells: 10,
+            tween_ms: 100,
+            ..Default::default()
+        },
+        input: InputConfig {
+            keyboard_device: None,
+            gamepad_device: None,
+            ..Default::default()
+        },
+        display: DisplayConfig {
+            target_monitor: "auto".to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut controller = MotionController::new(Arc::new(RwLock::new(config)));
+    controller.handle_event(MotionEvent::Jump {
+        target: JumpTarget::TopLeft,
+    });
+
+    // First dash stops at the edge, the next one hands off
+    controller.handle_event(MotionEvent::Dash {
+        direction: Direction::Right,
+    });
+    assert_eq!(controller.grid_position(), (4, 0));
+    assert_eq!(controller.take_handoff(), None);
+
+    controller.handle_event(MotionEvent::Move {
+        direction: Direction::Right,
+    });
+    assert_eq!(controller.take_handoff(), Some(Direction::Right));
+
+    // Entering the next output lands on its left edge
+    controller.enter_output(Direction::Right);
+    assert_eq!(controller.grid_position(), (0, 0));
+}
+
+#[tokio::test]
+async fn test_jump_to_cell() {
+    let config = Config {
+        grid: GridConfig {
+            cols: 10,
+            rows: 6,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut controller = MotionController::new(Arc::new(RwLock::new(config)));
+
+    controller.handle_event(MotionEvent::JumpTo { cell: (7, 3) });
+    assert_eq!(controller.grid_position(), (7, 3));
+
+    // Cells outside the grid are clamped
+    controller.handle_event(MotionEvent::JumpTo { cell: (50, 50) });
+    assert_eq!(controller.grid_position(), (9, 5));
+}
+
+#[tokio::test]
+async fn test_reposition_into_shrunk_grid() {
+    let config = Arc::new(RwLock::new(Config {
+        grid: GridConfig {
+            cols: 10,
+            rows: 6,
+            ..Default::default()
+        },
+        ..Default::default()
+    }));
+    let mut controller = MotionController::new(config.clone());
+    controller.handle_event(MotionEvent::JumpTo { cell: (8, 4) });
+
+    config.write().await.grid = GridConfig {
+        cols: 4,
+        rows: 3,
+        ..Default::default()
+    };
+    controller.reposition();
+    assert_eq!(controller.grid_position(), (3, 2));
+
+    // Snaps straight to the new bottom-right cell
+    assert_eq!(controller.update(), Some((1.0, 1.0)));
+    assert_eq!(controller.update(), None);
+}
+
+#[tokio::test]
+async fn test_grid_over_region() {
+    let config = Arc::new(RwLock::new(Config {
+        grid: GridConfig {
+            cols: 5,
+            rows: 3,
+            ..Default::default()
+        },
+        movement: MovementConfig {
+            tween_ms: 0,
+            ..Default::default()
+        },
+        ..Default::default()
+    }));
+    let mut controller = MotionController::new(config);
+    controller.handle_event(MotionEvent::JumpTo { cell: (4, 2) });
+
+    // A 960x540 window in the middle of a 1920x1080 output at (1920, 0)
+    let output = Geometry {
+        x: 1920,
+        y: 0,
+        width: 1920,
+        height: 1080,
+    };
+    let window = Geometry {
+        x: 2400,
+        y: 270,
+        width: 960,
+        height: 540,
+    };
+    let region = Region::of(window, output).unwrap();
+    assert_eq!(
+        region,
+        Region {
+            x: 0.25,
+            y: 0.25,
+            width: 0.5,
+            height: 0.5
+        }
+    );
+    controller.set_region(region);
+    controller.reposition();
+    assert_eq!(controller.grid_position(), (4, 2));
+    assert_eq!(controller.update(), Some((0.75, 0.75)));
+
+    controller.handle_event(MotionEvent::JumpTo { cell: (0, 0) });
+    controller.update();
+    assert_eq!(controller.screen_position(), (0.25, 0.25));
+
+    // Clipped to the output, and nothing when the window is elsewhere
+    let overhanging = Geometry { x: 1440, ..window };
+    assert_eq!(Region::of(overhanging, output).unwrap().x, 0.0);
+    let elsewhere = Geometry { x: 0, ..window };
+    assert_eq!(Region::of(elsewhere, output), None);
+}
+
+#[tokio::test]
+async fn test_custom_layouts() {
+    let config = Arc::new(RwLock::new(Config {
+        grid: GridConfig {
+            cols: 4,
+            rows: 2,
+            anchor: CellAnchor::Center,
+            ..Default::default()
+        },
+        movement: MovementConfig {
+            tween_ms: 0,
+            ..Default::default()
+        },
+        ..Default::default()
+    }));
+    let mut controller = MotionController::new(config.clone());
+    let mut position = |cell| {
+        controller.handle_event(MotionEvent::JumpTo { cell });
+        controller.reposition();
+        controller.update();
+        controller.screen_position()
+    };
+
+    // Cell centers never touch the edges
+    assert_eq!(position((0, 0)), (0.125, 0.25));
+    assert_eq!(position((3, 1)), (0.875, 0.75));
+
+    // Explicit boundaries, with a panel across the top taking 10% of the height
+    config.write().await.grid = GridConfig {
+        col_bounds: vec![0.0, 0.2, 0.8, 1.0],
+        row_bounds: vec![0.0, 0.5, 1.0],
+        margin_top: 0.1,
+        ..Default::default()
+    };
+    assert_eq!(config.read().await.grid.size(), (4, 3));
+    assert_eq!(position((1, 0)), (0.2, 0.1));
+    assert_eq!(position((3, 1)), (1.0, 0.55));
+
+    // Centered between the boundaries, one position fewer
+    config.write().await.grid.anchor = CellAnchor::Center;
+    assert_eq!(config.read().await.grid.size(), (3, 2));
+    assert_eq!(position((1, 1)), (0.5, 0.775));
+    assert_eq!(position((9, 9)), (0.9, 0.775));
+}
+
+#[tokio::test]
+async fn test_absolute_jumps() {
+    let config = Config {
+        grid: GridConfig {
+            cols: 20,
+            rows: 12,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut controller = MotionController::new(Arc::new(RwLock::new(config)));
+    assert_eq!(controller.grid_position(), (10, 6));
+
+    let jumps = [
+        (JumpTarget::BottomRight, (19, 11)),
+        (JumpTarget::RowStart, (0, 11)),
+        (JumpTarget::ColumnStart, (0, 0)),
+        (JumpTarget::Column(11), (11, 0)),
+        (JumpTarget::Row(4), (11, 4)),
+        (JumpTarget::Row(40), (11, 11)),
+        (JumpTarget::RowEnd, (19, 11)),
+        (JumpTarget::TopLeft, (0, 0)),
+        (JumpTarget::Center, (10, 6)),
+    ];
+
+    for (target, expected) in jumps {
+        controller.handle_event(MotionEvent::Jump { target });
+        assert_eq!(controller.grid_position(), expected, "{:?}", target);
+    }
+}
+
+#[tokio::test]
+async fn test_history_navigation() {
+    let config = Config {
+        grid: GridConfig {
+            cols: 10,
+            rows: 10,
+            ..Default::default()
+        },
+        movement: MovementConfig {
+            history_size: 3,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut controller = MotionController::new(Arc::new(RwLock::new(config)));
+
+    controller.handle_event(MotionEvent::JumpTo { cell: (1, 1) });
+    controller.handle_event(MotionEvent::JumpTo { cell: (2, 2) });
+    controller.handle_event(MotionEvent::JumpTo { cell: (3, 3) });
+
+    // Only the last three positions are kept
+    controller.handle_event(MotionEvent::Back);
+    assert_eq!(controller.grid_position(), (2, 2));
+    controller.handle_event(MotionEvent::Back);
+    assert_eq!(controller.grid_position(), (1, 1));
+    controller.handle_event(MotionEvent::Back);
+    assert_eq!(controller.grid_position(), (1, 1));
+
+    controller.handle_event(MotionEvent::Forward);
+    assert_eq!(controller.grid_position(), (2, 2));
+
+    // A new move drops the forward entries
+    controller.handle_event(MotionEvent::JumpTo { cell: (5, 5) });
+    controller.handle_event(MotionEvent::Forward);
+    assert_eq!(controller.grid_position(), (5, 5));
+    controller.handle_event(MotionEvent::Back);
+    assert_eq!(controller.grid_position(), (2, 2));
+}
+
+#[tokio::test]
+async fn test_golden_trajectory() {
+    let config = Config {
+        grid: GridConfig {
+            cols: 10,
+            rows: 10,
+            ..Default::default()
+        },
+        movement: MovementConfig {
+            tween_ms: 100,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let clock = ManualClock::new();
+    let mut controller = MotionController::with_clock(Arc::new(RwLock::new(config)), clock.clone());
+
+    // The first update places the cursor on the center cell
+    let start = (5.0 / 9.0, 5.0 / 9.0);
+    assert_eq!(controller.update(), Some(start));
+    assert!(controller.is_idle());
+    assert_eq!(controller.update(), None);
+
+    // One cell right over 100 ms, sampled every 25 ms on the ease-out curve
+    controller.handle_event(MotionEvent::Move {
+        direction: Direction::Right,
+    });
+    for eased in [0.0, 0.578125, 0.875, 0.984375] {
+        let (x, y) = controller.update().unwrap();
+        assert!((x - (start.0 + eased / 9.0)).abs() < 1e-9, "x = {}", x);
+        assert_eq!(y, start.1);
+        clock.advance(Duration::from_millis(25));
+    }
+
+    assert_eq!(controller.update(), Some((6.0 / 9.0, start.1)));
+    assert!(controller.is_idle());
+    assert_eq!(controller.screen_position(), (6.0 / 9.0, start.1));
+}
+
+#[test]
+fn test_tween_duration_modes() {
+    use gridpointer::config::DurationMode;
+    use gridpointer::motion::tween_duration;
+
+    let mut movement = MovementConfig {
+        tween_ms: 150,
+        ms_per_cell: 20,
+        speed_px_per_sec: 2000.0,
+        min_tween_ms: 50,
+        max_tween_ms: 300,
+        ..Default::default()
+    };
+
+    // Fixed ignores distance
+    assert_eq!(
+        tween_duration(&movement, 1.0, 96.0),
+        Duration::from_millis(150)
+    );
+    assert_eq!(
+        tween_duration(&movement, 19.0, 1824.0),
+        Duration::from_millis(150)
+    );
+
+    // Per-cell scales with cells and clamps at both ends
+    movement.duration_mode = DurationMode::PerCell;
+    assert_eq!(
+        tween_duration(&movement, 1.0, 96.0),
+        Duration::from_millis(50)
+    );
+    assert_eq!(
+        tween_duration(&movement, 5.0, 480.0),
+        Duration::from_millis(100)
+    );
+    assert_eq!(
+        tween_duration(&movement, 19.0, 1824.0),
+        Duration::from_millis(300)
+    );
+
+    // Speed scales with pixels travelled
+    movement.duration_mode = DurationMode::Speed;
+    assert_eq!(
+        tween_duration(&movement, 4.0, 400.0),
+        Duration::from_millis(200)
+    );
+    assert_eq!(
+        tween_duration(&movement, 1.0, 20.0),
+        Duration::from_millis(50)
+    );
+
+    // Distances from a degenerate grid fall back to `tween_ms` instead of panicking
+    assert_eq!(
+        tween_duration(&movement, f64::NAN, f64::NAN),
+        Duration::from_millis(150)
+    );
+    movement.duration_mode = DurationMode::PerCell;
+    assert_eq!(
+        tween_duration(&movement, f64::INFINITY, 0.0),
+        Duration::from_millis(150)
+    );
 }
 
 #[test]
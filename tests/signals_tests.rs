@@ -0,0 +1,42 @@
+//! Tests for signal handling
+
+use gridpointer::ipc::{Request, Response};
+use gridpointer::signals::Signals;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+
+fn raise(signal: &str) {
+    let status = std::process::Command::new("kill")
+        .arg(format!("-{}", signal))
+        .arg(std::process::id().to_string())
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[tokio::test]
+async fn test_signals_become_requests() {
+    let signals = Signals::new().unwrap();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+    let handle = tokio::spawn(signals.run(tx, shutdown_rx));
+
+    for (signal, expected) in [
+        ("HUP", Request::Reload),
+        ("USR1", Request::Pause),
+        ("USR2", Request::Resume),
+        ("TERM", Request::Quit),
+        ("INT", Request::Quit),
+    ] {
+        raise(signal);
+        let (request, reply) = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(request, expected, "SIG{}", signal);
+        reply.send(Response::ok()).unwrap();
+    }
+
+    shutdown_tx.send(()).unwrap();
+    handle.await.unwrap().unwrap();
+}
@@ -0,0 +1,79 @@
+//! Tests for the systemd notify protocol and socket activation
+
+use gridpointer::ipc::{self, IpcServer, Request, Response};
+use gridpointer::systemd;
+use std::ffi::OsStr;
+use std::os::unix::net::{UnixDatagram, UnixListener};
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::sync::{broadcast, mpsc};
+
+#[test]
+fn test_notify_fake_socket() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("notify");
+    let socket = UnixDatagram::bind(&path).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let mut buf = [0; 256];
+    let mut receive = || {
+        let len = socket.recv(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    };
+
+    for state in ["READY=1\nSTATUS=Grid mode on", "WATCHDOG=1", "STOPPING=1"] {
+        assert!(systemd::notify_to(Some(path.as_os_str()), state).unwrap());
+        assert_eq!(receive(), state);
+    }
+
+    // Not running under systemd
+    assert!(!systemd::notify_to(None, "READY=1").unwrap());
+}
+
+#[test]
+fn test_watchdog_interval() {
+    let interval = |usec: Option<&str>, pid: Option<&str>| {
+        systemd::watchdog_interval_from(usec.map(OsStr::new), pid.map(OsStr::new))
+    };
+    let pid = std::process::id().to_string();
+    assert_eq!(
+        interval(Some("10000000"), Some(&pid)),
+        Some(Duration::from_secs(5))
+    );
+    assert_eq!(
+        interval(Some("10000000"), None),
+        Some(Duration::from_secs(5))
+    );
+
+    // Meant for another process
+    assert_eq!(interval(Some("10000000"), Some("1")), None);
+    assert_eq!(interval(None, None), None);
+    assert_eq!(interval(Some("0"), None), None);
+}
+
+#[tokio::test]
+async fn test_activated_control_socket() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("gridpointer.sock");
+
+    // Created by the service manager and handed over already listening
+    let server = IpcServer::from_std(UnixListener::bind(&path).unwrap()).unwrap();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (events, _) = broadcast::channel(16);
+    let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+    let handle = tokio::spawn(server.run(tx, events, shutdown_rx));
+    tokio::spawn(async move {
+        while let Some((_, reply)) = rx.recv().await {
+            let _ = reply.send(Response::ok());
+        }
+    });
+
+    assert!(ipc::send_request(&path, &Request::Toggle).await.unwrap().ok);
+
+    // The socket outlives the daemon so it can be activated again
+    shutdown_tx.send(()).unwrap();
+    handle.await.unwrap().unwrap();
+    assert!(path.exists());
+}
@@ -0,0 +1,233 @@
+//! Tests for output discovery against a minimal fake compositor
+
+use gridpointer::config::{ConfigChange, ConfigManager};
+use gridpointer::input::Direction;
+use gridpointer::wl::WaylandManager;
+use std::io::{Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Outputs the fake compositor advertises: connector name, logical position,
+/// mode in physical pixels and scale
+///
+/// The scaled laptop panel is 1440x900 in the layout, so DP-1 starts at 1440.
+const OUTPUTS: &[(&str, i32, i32, i32, i32, i32)] = &[
+    ("eDP-1", 0, 0, 2880, 1800, 2),
+    ("DP-1", 1440, 0, 2560, 1440, 1),
+];
+
+/// Other globals, numbered after the outputs
+const GLOBALS: &[&str] = &["wl_seat", "zwlr_virtual_pointer_manager_v1"];
+
+/// Arguments of each `motion_absolute`: x, y and the layout's extent
+type Motions = Arc<Mutex<Vec<[u32; 4]>>>;
+
+/// Arguments of one wire message, encoded as the protocol lays them out
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn uint(mut self, value: u32) -> Self {
+        self.0.extend_from_slice(&value.to_ne_bytes());
+        self
+    }
+
+    fn int(self, value: i32) -> Self {
+        self.uint(value as u32)
+    }
+
+    fn string(mut self, value: &str) -> Self {
+        let len = value.len() + 1;
+        self = self.uint(len as u32);
+        self.0.extend_from_slice(value.as_bytes());
+        self.0.resize(self.0.len() + 1 + (4 - len % 4) % 4, 0);
+        self
+    }
+
+    fn send(self, stream: &mut UnixStream, object: u32, opcode: u16) {
+        let size = (8 + self.0.len()) as u32;
+        let mut bytes = Message::default()
+            .uint(object)
+            .uint(size << 16 | opcode as u32)
+            .0;
+        bytes.extend(self.0);
+        stream.write_all(&bytes).unwrap();
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_ne_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+/// Serve one client: globals for the outputs, their details once bound,
+/// virtual pointer motions, and roundtrips
+fn serve(mut stream: UnixStream, motions: Motions) {
+    let mut registry = None;
+    let mut pointer_manager = None;
+    let mut pointer = None;
+    let mut header = [0; 8];
+    while stream.read_exact(&mut header).is_ok() {
+        let object = read_u32(&header, 0);
+        let size = (read_u32(&header, 4) >> 16) as usize;
+        let opcode = read_u32(&header, 4) & 0xffff;
+        let mut args = vec![0; size - 8];
+        stream.read_exact(&mut args).unwrap();
+
+        match (object, opcode) {
+            // wl_display.sync: done, then free the callback id
+            (1, 0) => {
+                let callback = read_u32(&args, 0);
+                Message::default().uint(0).send(&mut stream, callback, 0);
+                Message::default().uint(callback).send(&mut stream, 1, 1);
+            }
+            // wl_display.get_registry: one wl_output global per output, then the rest
+            (1, 1) => {
+                let id = read_u32(&args, 0);
+                registry = Some(id);
+                let interfaces = OUTPUTS.iter().map(|_| ("wl_output", 4));
+                let others = GLOBALS.iter().map(|interface| (*interface, 1));
+                for (name, (interface, version)) in interfaces.chain(others).enumerate() {
+                    Message::default()
+                        .uint(name as u32 + 1)
+                        .string(interface)
+                        .uint(version)
+                        .send(&mut stream, id, 0);
+                }
+            }
+            // wl_registry.bind(name, interface, version, id)
+            (object, 0) if Some(object) == registry => {
+                let global = read_u32(&args, 0) as usize;
+                let len = read_u32(&args, 4) as usize;
+                let version_at = 8 + len.div_ceil(4) * 4;
+                let version = read_u32(&args, version_at);
+                let id = read_u32(&args, version_at + 4);
+                let Some(&(name, x, y, width, height, scale)) = OUTPUTS.get(global - 1) else {
+                    if GLOBALS[global - 1 - OUTPUTS.len()] == "zwlr_virtual_pointer_manager_v1" {
+                        pointer_manager = Some(id);
+                    }
+                    continue;
+                };
+                Message::default()
+                    .int(x)
+                    .int(y)
+                    .int(300)
+                    .int(200)
+                    .int(0)
+                    .string("Fake")
+                    .string(name)
+                    .int(0)
+                    .send(&mut stream, id, 0);
+                // The current and preferred mode, then another supported one
+                Message::default()
+                    .uint(3)
+                    .int(width)
+                    .int(height)
+                    .int(60_000)
+                    .send(&mut stream, id, 1);
+                Message::default()
+                    .uint(0)
+                    .int(1280)
+                    .int(720)
+                    .int(30_000)
+                    .send(&mut stream, id, 1);
+                Message::default().int(scale).send(&mut stream, id, 3);
+                // Like a real compositor, only name outputs bound at version 4
+                if version >= 4 {
+                    Message::default().string(name).send(&mut stream, id, 4);
+                }
+                Message::default().send(&mut stream, id, 2);
+            }
+            // zwlr_virtual_pointer_manager_v1.create_virtual_pointer(seat, id)
+            (object, 0) if Some(object) == pointer_manager => {
+                pointer = Some(read_u32(&args, 4));
+            }
+            // zwlr_virtual_pointer_v1.motion_absolute(time, x, y, x_extent, y_extent)
+            (object, 1) if Some(object) == pointer => {
+                let arg = |i: usize| read_u32(&args, 4 + i * 4);
+                motions
+                    .lock()
+                    .unwrap()
+                    .push([arg(0), arg(1), arg(2), arg(3)]);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Start the fake compositor at `path` and point Wayland clients at it
+fn fake_compositor(path: &Path) -> Motions {
+    let listener = UnixListener::bind(path).unwrap();
+    let motions = Motions::default();
+    std::thread::spawn({
+        let motions = motions.clone();
+        move || {
+            if let Ok((stream, _)) = listener.accept() {
+                serve(stream, motions);
+            }
+        }
+    });
+    // This is the only test in this binary, so nothing else reads the environment
+    unsafe {
+        std::env::remove_var("WAYLAND_SOCKET");
+        std::env::set_var("WAYLAND_DISPLAY", path);
+    }
+    motions
+}
+
+/// The next pointer motion the compositor receives
+async fn next_motion(motions: &Motions) -> [u32; 4] {
+    for _ in 0..100 {
+        if let Some(motion) = motions.lock().unwrap().pop() {
+            return motion;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("no pointer motion received");
+}
+
+#[tokio::test]
+async fn test_outputs_are_named_by_connector() {
+    let dir = TempDir::new().unwrap();
+    let motions = fake_compositor(&dir.path().join("wayland-0"));
+
+    let mut wayland = WaylandManager::new().await.unwrap();
+    assert_eq!(wayland.active_output_name().as_deref(), Some("eDP-1"));
+    assert_eq!(wayland.active_output_connector().as_deref(), Some("eDP-1"));
+
+    // Positions are logical, so the scaled panel's neighbour is found by its logical width
+    assert!(wayland.switch_output(Direction::Right));
+    assert_eq!(wayland.active_output_connector().as_deref(), Some("DP-1"));
+    assert!(wayland.switch_output(Direction::Left));
+    assert_eq!(wayland.active_output_connector().as_deref(), Some("eDP-1"));
+    assert!(!wayland.switch_output(Direction::Left));
+
+    // Pointer motions are in logical pixels of the whole layout
+    wayland.move_cursor(0.5, 0.5).await.unwrap();
+    assert_eq!(next_motion(&motions).await, [720, 450, 4000, 1440]);
+
+    assert!(wayland.focus_output("DP-1"));
+    assert_eq!(wayland.get_primary_output_size(), (2560, 1440));
+    assert_eq!(wayland.refresh_rate(), Some(60.0));
+    wayland.move_cursor(0.5, 0.5).await.unwrap();
+    assert_eq!(next_motion(&motions).await, [2720, 720, 4000, 1440]);
+    assert!(!wayland.focus_output("HDMI-A-1"));
+    assert_eq!(wayland.active_output_connector().as_deref(), Some("DP-1"));
+
+    // A reload naming a connector as target_monitor finds that output
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, "[display]\ntarget_monitor = \"auto\"\n").unwrap();
+    let manager = ConfigManager::with_path(path.clone()).await.unwrap();
+    assert!(wayland.focus_target("auto"));
+    assert_eq!(wayland.active_output_connector().as_deref(), Some("eDP-1"));
+
+    std::fs::write(&path, "[display]\ntarget_monitor = \"DP-1\"\n").unwrap();
+    let changes = manager.reload().await.unwrap();
+    assert!(changes.contains(&ConfigChange::TargetMonitor));
+    let config = manager.get_config();
+    assert!(wayland.focus_target(&config.read().await.display.target_monitor));
+    assert_eq!(wayland.active_output_connector().as_deref(), Some("DP-1"));
+    assert_eq!(wayland.get_primary_output_size(), (2560, 1440));
+}